@@ -1,8 +1,53 @@
 CREATE SCHEMA IF NOT EXISTS scrap;
 
+CREATE TABLE IF NOT EXISTS scrap.ctf (
+	slug TEXT PRIMARY KEY,
+	current BOOLEAN NOT NULL DEFAULT 'false',
+	title TEXT NOT NULL,
+	home TEXT NOT NULL,
+	start TIMESTAMP WITH TIME ZONE,
+	stop TIMESTAMP WITH TIME ZONE,
+	session_idle INTERVAL NOT NULL DEFAULT '1 day',
+	session_lifetime INTERVAL NOT NULL DEFAULT '30 days',
+	team_size INTEGER,
+	require_admin_totp BOOLEAN NOT NULL DEFAULT 'false',
+	notified_start TIMESTAMP WITH TIME ZONE,
+	notified_stop TIMESTAMP WITH TIME ZONE,
+	ticket_points INTEGER NOT NULL DEFAULT 50,
+	archived TIMESTAMP WITH TIME ZONE
+);
+
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS session_idle INTERVAL NOT NULL DEFAULT '1 day';
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS session_lifetime INTERVAL NOT NULL DEFAULT '30 days';
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS team_size INTEGER;
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS require_admin_totp BOOLEAN NOT NULL DEFAULT 'false';
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS notified_start TIMESTAMP WITH TIME ZONE;
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS notified_stop TIMESTAMP WITH TIME ZONE;
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS ticket_points INTEGER NOT NULL DEFAULT 50;
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS slug TEXT;
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS current BOOLEAN NOT NULL DEFAULT 'false';
+ALTER TABLE scrap.ctf ADD COLUMN IF NOT EXISTS archived TIMESTAMP WITH TIME ZONE;
+
+-- There used to be a single CTF in the row with id 1. It becomes the current CTF, named `ctf` if it had no slug.
+DO $$ BEGIN
+	IF EXISTS (SELECT FROM information_schema.columns
+		WHERE table_schema='scrap' AND table_name='ctf' AND column_name='id') THEN
+		UPDATE scrap.ctf SET slug=COALESCE(slug, 'ctf'), current=true;
+		ALTER TABLE scrap.ctf DROP COLUMN id;
+		ALTER TABLE scrap.ctf ADD PRIMARY KEY (slug);
+	END IF;
+END $$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS ctf_current_index ON scrap.ctf (current) WHERE current;
+
+CREATE OR REPLACE FUNCTION current_ctf() RETURNS TEXT AS $$
+SELECT slug FROM scrap.ctf WHERE current;
+$$ LANGUAGE sql STABLE;
+
 CREATE TABLE IF NOT EXISTS scrap.challenge (
-	id SERIAL PRIMARY KEY CHECK (id <= 64),
-	slug TEXT NOT NULL UNIQUE,
+	id SERIAL PRIMARY KEY,
+	ctf TEXT NOT NULL REFERENCES scrap.ctf ON UPDATE CASCADE DEFAULT current_ctf(),
+	slug TEXT NOT NULL,
 	title TEXT NOT NULL,
 	author TEXT NOT NULL,
 	value INTEGER NOT NULL,
@@ -10,68 +55,389 @@ CREATE TABLE IF NOT EXISTS scrap.challenge (
 	tags TEXT[],
 	flag TEXT NOT NULL,
 	enabled BOOLEAN,
-	solves INTEGER DEFAULT 0
+	solves INTEGER DEFAULT 0,
+	event TEXT,
+	category TEXT,
+	difficulty TEXT,
+	files TEXT[],
+	hints TEXT[]
 );
 
+ALTER TABLE scrap.challenge ADD COLUMN IF NOT EXISTS event TEXT;
+ALTER TABLE scrap.challenge ADD COLUMN IF NOT EXISTS category TEXT;
+ALTER TABLE scrap.challenge ADD COLUMN IF NOT EXISTS difficulty TEXT;
+ALTER TABLE scrap.challenge ADD COLUMN IF NOT EXISTS files TEXT[];
+ALTER TABLE scrap.challenge ADD COLUMN IF NOT EXISTS hints TEXT[];
+ALTER TABLE scrap.challenge ADD COLUMN IF NOT EXISTS ctf TEXT REFERENCES scrap.ctf ON UPDATE CASCADE DEFAULT current_ctf();
+UPDATE scrap.challenge SET ctf=current_ctf() WHERE ctf IS NULL;
+ALTER TABLE scrap.challenge ALTER COLUMN ctf SET NOT NULL;
+-- Challenge slugs are only unique within a CTF, and ids no longer index a 64 bit mask of solves.
+ALTER TABLE scrap.challenge DROP CONSTRAINT IF EXISTS challenge_id_check;
+ALTER TABLE scrap.challenge DROP CONSTRAINT IF EXISTS challenge_slug_key;
+CREATE UNIQUE INDEX IF NOT EXISTS challenge_ctf_slug_index ON scrap.challenge (ctf, slug);
+
 CREATE TABLE IF NOT EXISTS scrap.team (
 	id SERIAL PRIMARY KEY,
 	name TEXT NOT NULL UNIQUE,
 	discord TEXT NOT NULL UNIQUE,
 	hash TEXT NOT NULL,
-	solves BIGINT DEFAULT 0,
 	score INTEGER DEFAULT 0,
 	redeemed_score INTEGER DEFAULT 0,
 	premium_tickets INTEGER DEFAULT 0,
 	isadmin BOOLEAN DEFAULT 'false',
-	submit TIMESTAMP DEFAULT NOW()
+	submit TIMESTAMP DEFAULT NOW(),
+	invite TEXT NOT NULL UNIQUE DEFAULT encode(gen_random_bytes(8), 'hex'),
+	hidden BOOLEAN NOT NULL DEFAULT 'false',
+	banned BOOLEAN NOT NULL DEFAULT 'false'
 );
 
+ALTER TABLE scrap.team ADD COLUMN IF NOT EXISTS invite TEXT NOT NULL UNIQUE DEFAULT encode(gen_random_bytes(8), 'hex');
+ALTER TABLE scrap.team ADD COLUMN IF NOT EXISTS hidden BOOLEAN NOT NULL DEFAULT 'false';
+ALTER TABLE scrap.team ADD COLUMN IF NOT EXISTS banned BOOLEAN NOT NULL DEFAULT 'false';
+ALTER TABLE scrap.team ALTER COLUMN discord DROP NOT NULL;
+ALTER TABLE scrap.team ALTER COLUMN hash DROP NOT NULL;
+
+CREATE TABLE IF NOT EXISTS scrap.division (
+	slug TEXT PRIMARY KEY,
+	name TEXT NOT NULL,
+	email_domain TEXT,
+	approval BOOLEAN NOT NULL DEFAULT 'false',
+	position INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS scrap.member (
+	id SERIAL PRIMARY KEY,
+	name TEXT NOT NULL UNIQUE,
+	discord TEXT NOT NULL UNIQUE,
+	hash TEXT,
+	team INTEGER REFERENCES scrap.team ON DELETE SET NULL,
+	captain BOOLEAN NOT NULL DEFAULT 'false',
+	isadmin BOOLEAN NOT NULL DEFAULT 'false',
+	joined TIMESTAMP WITH TIME ZONE,
+	discord_id TEXT UNIQUE,
+	totp BYTEA,
+	totp_pending BYTEA,
+	totp_step BIGINT,
+	totp_failures INTEGER NOT NULL DEFAULT 0,
+	totp_failed TIMESTAMP WITH TIME ZONE,
+	division TEXT REFERENCES scrap.division ON DELETE SET NULL,
+	email TEXT,
+	division_approved BOOLEAN NOT NULL DEFAULT 'false'
+);
+
+ALTER TABLE scrap.member ADD COLUMN IF NOT EXISTS discord_id TEXT UNIQUE;
+ALTER TABLE scrap.member ADD COLUMN IF NOT EXISTS totp BYTEA;
+ALTER TABLE scrap.member ADD COLUMN IF NOT EXISTS totp_pending BYTEA;
+ALTER TABLE scrap.member ADD COLUMN IF NOT EXISTS totp_step BIGINT;
+ALTER TABLE scrap.member ADD COLUMN IF NOT EXISTS totp_failures INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE scrap.member ADD COLUMN IF NOT EXISTS totp_failed TIMESTAMP WITH TIME ZONE;
+ALTER TABLE scrap.member ADD COLUMN IF NOT EXISTS division TEXT REFERENCES scrap.division ON DELETE SET NULL;
+ALTER TABLE scrap.member ADD COLUMN IF NOT EXISTS email TEXT;
+ALTER TABLE scrap.member ADD COLUMN IF NOT EXISTS division_approved BOOLEAN NOT NULL DEFAULT 'false';
+ALTER TABLE scrap.member ALTER COLUMN hash DROP NOT NULL;
+
 CREATE TABLE IF NOT EXISTS scrap.prize (
-	id TEXT NOT NULL UNIQUE,
+	id SERIAL PRIMARY KEY,
 	team INTEGER NOT NULL REFERENCES scrap.team ON DELETE CASCADE,
-	prize TEXT NOT NULL
+	prize TEXT NOT NULL,
+	UNIQUE (team, prize)
 );
 
+-- One-time prizes used to be keyed by the team id and prize name run together.
+DO $$ BEGIN
+	IF EXISTS (SELECT FROM information_schema.columns
+		WHERE table_schema='scrap' AND table_name='prize' AND column_name='id' AND data_type='text') THEN
+		ALTER TABLE scrap.prize DROP COLUMN id;
+		ALTER TABLE scrap.prize ADD COLUMN id SERIAL PRIMARY KEY;
+		ALTER TABLE scrap.prize ADD UNIQUE (team, prize);
+	END IF;
+END $$;
+
 CREATE TABLE IF NOT EXISTS scrap.raffle (
 	id SERIAL PRIMARY KEY,
-	team INTEGER NOT NULL REFERENCES scrap.team ON DELETE CASCADE
+	team INTEGER NOT NULL REFERENCES scrap.team ON DELETE CASCADE,
+	member INTEGER REFERENCES scrap.member ON DELETE SET NULL,
+	time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE scrap.raffle ADD COLUMN IF NOT EXISTS member INTEGER REFERENCES scrap.member ON DELETE SET NULL;
+ALTER TABLE scrap.raffle ADD COLUMN IF NOT EXISTS time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+
+CREATE TABLE IF NOT EXISTS scrap.draw (
+	id SERIAL PRIMARY KEY,
+	team INTEGER NOT NULL REFERENCES scrap.team ON DELETE CASCADE,
+	member INTEGER REFERENCES scrap.member ON DELETE SET NULL,
+	prize TEXT NOT NULL,
+	time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+	fulfilled TIMESTAMP WITH TIME ZONE
+);
+
+ALTER TABLE scrap.draw ADD COLUMN IF NOT EXISTS fulfilled TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE IF NOT EXISTS scrap.inventory (
+	id SERIAL PRIMARY KEY,
+	name TEXT NOT NULL UNIQUE,
+	quantity INTEGER NOT NULL CHECK (quantity >= 0)
+);
+
+-- Raffle drawings keep their seed, eligible entries and prize units so the winners can be recomputed.
+CREATE TABLE IF NOT EXISTS scrap.drawing (
+	id SERIAL PRIMARY KEY,
+	seed TEXT NOT NULL,
+	entries INTEGER[] NOT NULL,
+	admin INTEGER REFERENCES scrap.member ON DELETE SET NULL,
+	time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+	prizes TEXT[]
+);
+
+ALTER TABLE scrap.drawing ADD COLUMN IF NOT EXISTS prizes TEXT[];
+
+CREATE TABLE IF NOT EXISTS scrap.winner (
+	id SERIAL PRIMARY KEY,
+	drawing INTEGER NOT NULL REFERENCES scrap.drawing ON DELETE CASCADE,
+	entry INTEGER NOT NULL UNIQUE REFERENCES scrap.raffle ON DELETE CASCADE,
+	inventory INTEGER NOT NULL REFERENCES scrap.inventory,
+	fulfilled TIMESTAMP WITH TIME ZONE
 );
 
 CREATE TABLE IF NOT EXISTS scrap.session (
 	cookie TEXT PRIMARY KEY,
-	team INTEGER NOT NULL REFERENCES scrap.team ON DELETE CASCADE
+	team INTEGER REFERENCES scrap.team ON DELETE CASCADE,
+	member INTEGER REFERENCES scrap.member ON DELETE CASCADE,
+	id SERIAL UNIQUE,
+	agent TEXT,
+	created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+	seen TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
 );
 
-CREATE TABLE IF NOT EXISTS scrap.ctf (
-	id INTEGER NOT NULL UNIQUE CHECK (id = 1) DEFAULT 1,
-	title TEXT NOT NULL,
-	home TEXT NOT NULL,
-	start TIMESTAMP WITH TIME ZONE,
-	stop TIMESTAMP WITH TIME ZONE
+ALTER TABLE scrap.session ADD COLUMN IF NOT EXISTS id SERIAL UNIQUE;
+ALTER TABLE scrap.session ADD COLUMN IF NOT EXISTS agent TEXT;
+ALTER TABLE scrap.session ADD COLUMN IF NOT EXISTS created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+ALTER TABLE scrap.session ADD COLUMN IF NOT EXISTS seen TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+ALTER TABLE scrap.session ADD COLUMN IF NOT EXISTS member INTEGER REFERENCES scrap.member ON DELETE CASCADE;
+ALTER TABLE scrap.session ALTER COLUMN team DROP NOT NULL;
+
+-- Teams used to be the only accounts, so each existing team becomes its own captain.
+DO $$ BEGIN
+	IF NOT EXISTS (SELECT FROM scrap.member) THEN
+		INSERT INTO scrap.member (name, discord, hash, team, captain, isadmin)
+		SELECT name, discord, hash, id, true, COALESCE(isadmin, false) FROM scrap.team
+		WHERE discord IS NOT NULL AND hash IS NOT NULL;
+		UPDATE scrap.session session SET member=member.id
+		FROM scrap.member member
+		WHERE member.team=session.team AND session.member IS NULL;
+	END IF;
+END $$;
+
+CREATE TABLE IF NOT EXISTS scrap.reset (
+	hash TEXT PRIMARY KEY,
+	member INTEGER NOT NULL REFERENCES scrap.member ON DELETE CASCADE,
+	expires TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS scrap.recovery (
+	member INTEGER NOT NULL REFERENCES scrap.member ON DELETE CASCADE,
+	hash TEXT NOT NULL,
+	PRIMARY KEY (member, hash)
+);
+
+CREATE TABLE IF NOT EXISTS scrap.pending (
+	cookie TEXT PRIMARY KEY,
+	member INTEGER NOT NULL REFERENCES scrap.member ON DELETE CASCADE,
+	agent TEXT,
+	attempts INTEGER NOT NULL DEFAULT 0,
+	created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS scrap.token (
+	id SERIAL PRIMARY KEY,
+	member INTEGER NOT NULL REFERENCES scrap.member ON DELETE CASCADE,
+	name TEXT NOT NULL,
+	hash TEXT NOT NULL UNIQUE,
+	created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS scrap.solve (
+	team INTEGER NOT NULL REFERENCES scrap.team ON DELETE CASCADE,
+	member INTEGER REFERENCES scrap.member ON DELETE SET NULL,
+	challenge INTEGER NOT NULL REFERENCES scrap.challenge ON DELETE CASCADE,
+	time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+	ctf TEXT NOT NULL REFERENCES scrap.ctf ON UPDATE CASCADE DEFAULT current_ctf(),
+	PRIMARY KEY (team, challenge)
+);
+
+ALTER TABLE scrap.solve ADD COLUMN IF NOT EXISTS ctf TEXT REFERENCES scrap.ctf ON UPDATE CASCADE DEFAULT current_ctf();
+UPDATE scrap.solve solve SET ctf=challenge.ctf FROM scrap.challenge challenge
+	WHERE challenge.id=solve.challenge AND solve.ctf IS NULL;
+ALTER TABLE scrap.solve ALTER COLUMN ctf SET NOT NULL;
+
+-- Solves used to be kept only as a bit mask of challenge ids on each team.
+DO $$ BEGIN
+	IF EXISTS (SELECT FROM information_schema.columns
+		WHERE table_schema='scrap' AND table_name='team' AND column_name='solves') THEN
+		INSERT INTO scrap.solve (team, challenge, time, ctf)
+		SELECT team.id, challenge.id, team.submit, challenge.ctf
+		FROM scrap.team team, scrap.challenge challenge
+		WHERE challenge.id <= 64 AND ((COALESCE(team.solves, 0) >> (challenge.id - 1)) & 1)=1
+		ON CONFLICT DO NOTHING;
+		ALTER TABLE scrap.team DROP COLUMN solves;
+	END IF;
+END $$;
+
+-- Flags found after the CTF ends, kept apart from the scoreboard.
+CREATE TABLE IF NOT EXISTS scrap.practice (
+	team INTEGER NOT NULL REFERENCES scrap.team ON DELETE CASCADE,
+	member INTEGER REFERENCES scrap.member ON DELETE SET NULL,
+	challenge INTEGER NOT NULL REFERENCES scrap.challenge ON DELETE CASCADE,
+	time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (team, challenge)
+);
+
+-- Wrong flags, shown to admins on team pages.
+CREATE TABLE IF NOT EXISTS scrap.submission (
+	id SERIAL PRIMARY KEY,
+	team INTEGER NOT NULL REFERENCES scrap.team ON DELETE CASCADE,
+	member INTEGER REFERENCES scrap.member ON DELETE SET NULL,
+	challenge INTEGER NOT NULL REFERENCES scrap.challenge ON DELETE CASCADE,
+	flag TEXT NOT NULL,
+	time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS submission_team_time_index ON scrap.submission (team, time);
+
+CREATE TABLE IF NOT EXISTS scrap.adjustment (
+	id SERIAL PRIMARY KEY,
+	team INTEGER NOT NULL REFERENCES scrap.team ON DELETE CASCADE,
+	delta INTEGER NOT NULL,
+	reason TEXT NOT NULL,
+	admin INTEGER REFERENCES scrap.member ON DELETE SET NULL,
+	time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+	ctf TEXT NOT NULL REFERENCES scrap.ctf ON UPDATE CASCADE DEFAULT current_ctf()
+);
+
+ALTER TABLE scrap.adjustment ADD COLUMN IF NOT EXISTS ctf TEXT REFERENCES scrap.ctf ON UPDATE CASCADE DEFAULT current_ctf();
+UPDATE scrap.adjustment SET ctf=current_ctf() WHERE ctf IS NULL;
+ALTER TABLE scrap.adjustment ALTER COLUMN ctf SET NOT NULL;
+
+CREATE TABLE IF NOT EXISTS scrap.announcement (
+	id SERIAL PRIMARY KEY,
+	message TEXT NOT NULL,
+	challenge INTEGER REFERENCES scrap.challenge ON DELETE CASCADE,
+	admin INTEGER REFERENCES scrap.member ON DELETE SET NULL,
+	time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+	ctf TEXT NOT NULL REFERENCES scrap.ctf ON UPDATE CASCADE DEFAULT current_ctf()
+);
+
+ALTER TABLE scrap.announcement ADD COLUMN IF NOT EXISTS ctf TEXT REFERENCES scrap.ctf ON UPDATE CASCADE DEFAULT current_ctf();
+UPDATE scrap.announcement SET ctf=current_ctf() WHERE ctf IS NULL;
+ALTER TABLE scrap.announcement ALTER COLUMN ctf SET NOT NULL;
+
+CREATE TABLE IF NOT EXISTS scrap.audit (
+	id SERIAL PRIMARY KEY,
+	admin INTEGER REFERENCES scrap.member ON DELETE SET NULL,
+	team INTEGER REFERENCES scrap.team ON DELETE SET NULL,
+	action TEXT NOT NULL,
+	reason TEXT,
+	time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Final standings of archived CTFs, frozen when the next CTF starts.
+CREATE TABLE IF NOT EXISTS scrap.standing (
+	ctf TEXT NOT NULL REFERENCES scrap.ctf ON UPDATE CASCADE ON DELETE CASCADE,
+	place BIGINT NOT NULL,
+	team INTEGER REFERENCES scrap.team ON DELETE SET NULL,
+	name TEXT NOT NULL,
+	score INTEGER NOT NULL,
+	solves BIGINT NOT NULL,
+	division TEXT,
+	PRIMARY KEY (ctf, place)
+);
+
+CREATE TABLE IF NOT EXISTS scrap.catalog (
+	name TEXT PRIMARY KEY,
+	weight DOUBLE PRECISION NOT NULL,
+	one_time BOOLEAN NOT NULL DEFAULT 'false',
+	position INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS scrap.grant (
+	position INTEGER PRIMARY KEY,
+	max_value INTEGER,
+	tickets INTEGER NOT NULL
 );
 
 CREATE TABLE IF NOT EXISTS scrap.event (
 	id INTEGER NOT NULL UNIQUE,
 	title TEXT NOT NULL,
 	short TEXT NOT NULL,
-	date TEXT NOT NULL,
 	description TEXT NOT NULL,
 	link TEXT,
-	slides TEXT
+	slides TEXT,
+	start TIMESTAMP WITH TIME ZONE,
+	stop TIMESTAMP WITH TIME ZONE,
+	utc_offset INTEGER NOT NULL DEFAULT 0,
+	location TEXT,
+	quarter TEXT,
+	slug TEXT UNIQUE,
+	recording TEXT,
+	files TEXT[]
 );
 
+ALTER TABLE scrap.event DROP COLUMN IF EXISTS date;
+ALTER TABLE scrap.event ADD COLUMN IF NOT EXISTS start TIMESTAMP WITH TIME ZONE;
+ALTER TABLE scrap.event ADD COLUMN IF NOT EXISTS stop TIMESTAMP WITH TIME ZONE;
+ALTER TABLE scrap.event ADD COLUMN IF NOT EXISTS utc_offset INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE scrap.event ADD COLUMN IF NOT EXISTS location TEXT;
+ALTER TABLE scrap.event ADD COLUMN IF NOT EXISTS quarter TEXT;
+ALTER TABLE scrap.event ADD COLUMN IF NOT EXISTS slug TEXT UNIQUE;
+ALTER TABLE scrap.event ADD COLUMN IF NOT EXISTS recording TEXT;
+ALTER TABLE scrap.event ADD COLUMN IF NOT EXISTS files TEXT[];
+
 CREATE INDEX IF NOT EXISTS team_name_hash_index ON scrap.team (name, hash);
-CREATE INDEX IF NOT EXISTS team_score_submit_index ON scrap.team (score DESC, submit ASC) INCLUDE (name, solves);
+CREATE INDEX IF NOT EXISTS team_score_submit_index ON scrap.team (score DESC, submit ASC) INCLUDE (name);
+CREATE INDEX IF NOT EXISTS solve_ctf_index ON scrap.solve (ctf);
 CREATE INDEX IF NOT EXISTS session_cookie_index ON scrap.session (cookie);
+CREATE INDEX IF NOT EXISTS session_member_index ON scrap.session (member);
+CREATE INDEX IF NOT EXISTS member_team_index ON scrap.member (team);
+CREATE INDEX IF NOT EXISTS member_name_hash_index ON scrap.member (name, hash);
+
+CREATE OR REPLACE FUNCTION expired(created TIMESTAMP WITH TIME ZONE, seen TIMESTAMP WITH TIME ZONE) RETURNS BOOLEAN AS $$
+SELECT seen < NOW() - session_idle OR created < NOW() - session_lifetime FROM scrap.ctf WHERE current;
+$$ LANGUAGE sql STABLE;
+
+-- API tokens are stored as digests, sessions by their cookie.
+CREATE OR REPLACE FUNCTION lookup_member(TEXT) RETURNS INTEGER AS $$
+SELECT member FROM scrap.session WHERE cookie=$1 AND NOT expired(created, seen)
+UNION ALL
+SELECT member FROM scrap.token WHERE hash=encode(digest($1, 'sha256'), 'hex')
+LIMIT 1;
+$$ LANGUAGE sql STABLE;
 
 CREATE OR REPLACE FUNCTION lookup(TEXT) RETURNS INTEGER AS $$
-SELECT team FROM scrap.session WHERE cookie=$1 LIMIT 1;
+SELECT team FROM scrap.member WHERE id=lookup_member($1);
+$$ LANGUAGE sql STABLE;
+
+-- A team ranks in a division only when every member is approved for that division.
+CREATE OR REPLACE FUNCTION team_division(INTEGER) RETURNS TEXT AS $$
+SELECT CASE WHEN bool_and(division_approved) AND COUNT(division)=COUNT(*) AND COUNT(DISTINCT division)=1
+	THEN MIN(division) END
+FROM scrap.member WHERE team=$1;
 $$ LANGUAGE sql STABLE;
 
-CREATE OR REPLACE FUNCTION solved(solves BIGINT, id INTEGER) RETURNS BOOLEAN AS $$
-SELECT ((COALESCE(solves, 0) >> (id - 1)) & 1)=1;
-$$ LANGUAGE sql IMMUTABLE;
+-- A drawing's winners as recomputed from its seed, entries and prize units.
+CREATE OR REPLACE FUNCTION drawing_results(INTEGER) RETURNS TABLE (entry INTEGER, prize TEXT) AS $$
+SELECT entries.entry, units.prize
+FROM (SELECT entry, ROW_NUMBER() OVER (ORDER BY digest(drawing.seed || ':' || entry, 'sha256')) AS rank
+	FROM scrap.drawing drawing, unnest(drawing.entries) entry
+	WHERE drawing.id=$1) entries
+JOIN (SELECT unit.prize, unit.rank
+	FROM scrap.drawing drawing, unnest(drawing.prizes) WITH ORDINALITY unit(prize, rank)
+	WHERE drawing.id=$1) units ON units.rank=entries.rank;
+$$ LANGUAGE sql STABLE;
+
+DROP FUNCTION IF EXISTS solved(BIGINT, INTEGER);
+DROP FUNCTION IF EXISTS update(BIGINT, INTEGER);
 
-CREATE OR REPLACE FUNCTION update(solves BIGINT, id INTEGER) RETURNS BIGINT AS $$
-SELECT solves | (CAST(1 AS BIGINT) << (id - 1));
-$$ LANGUAGE sql IMMUTABLE;
+CREATE OR REPLACE FUNCTION solved(team INTEGER, challenge INTEGER) RETURNS BOOLEAN AS $$
+SELECT EXISTS (SELECT FROM scrap.solve WHERE solve.team=$1 AND solve.challenge=$2);
+$$ LANGUAGE sql STABLE;
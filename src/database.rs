@@ -1,16 +1,103 @@
 use std::error::Error;
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+use std::fs;
+use std::path::PathBuf;
 use r2d2_postgres::PostgresConnectionManager;
-use r2d2_postgres::postgres::NoTls;
 use r2d2_postgres::r2d2::{Pool, PooledConnection};
 
-pub type ClientPool = Pool<PostgresConnectionManager<NoTls>>;
-pub type Client = PooledConnection<PostgresConnectionManager<NoTls>>;
+#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+type Tls = postgres_native_tls::MakeTlsConnector;
+#[cfg(feature = "rustls")]
+type Tls = tokio_postgres_rustls::MakeRustlsConnect;
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+type Tls = r2d2_postgres::postgres::NoTls;
 
-pub fn connect(uri: &str) -> Result<ClientPool, Box<dyn Error>> {
-	let manager = PostgresConnectionManager::new(uri.parse()?, NoTls);
+pub type ClientPool = Pool<PostgresConnectionManager<Tls>>;
+pub type Client = PooledConnection<PostgresConnectionManager<Tls>>;
+
+/// Certificates used when the URI's `sslmode` asks for an encrypted connection.
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+	pub ca_cert: Option<PathBuf>,
+	pub client_cert: Option<PathBuf>,
+	pub client_key: Option<PathBuf>,
+}
+
+#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+fn make_tls(config: &TlsConfig) -> Result<Tls, Box<dyn Error>> {
+	use native_tls::{Certificate, Identity, TlsConnector};
+
+	let mut builder = TlsConnector::builder();
+	if let Some(path) = &config.ca_cert {
+		builder.add_root_certificate(Certificate::from_pem(&fs::read(path)?)?);
+	}
+	if let (Some(cert), Some(key)) = (&config.client_cert, &config.client_key) {
+		builder.identity(Identity::from_pkcs8(&fs::read(cert)?, &fs::read(key)?)?);
+	}
+	Ok(postgres_native_tls::MakeTlsConnector::new(builder.build()?))
+}
+
+#[cfg(feature = "rustls")]
+fn make_tls(config: &TlsConfig) -> Result<Tls, Box<dyn Error>> {
+	use std::sync::Arc;
+	use rustls::{ClientConfig, RootCertStore};
+	use rustls::crypto::ring::default_provider;
+
+	let mut roots = RootCertStore::empty();
+	match &config.ca_cert {
+		Some(path) => for cert in rustls_pemfile::certs(&mut fs::read(path)?.as_slice()) {
+			roots.add(cert?)?;
+		},
+		None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+	}
+	let builder = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+		.with_safe_default_protocol_versions()?
+		.with_root_certificates(roots);
+	let tls = match (&config.client_cert, &config.client_key) {
+		(Some(cert), Some(key)) => {
+			let certs = rustls_pemfile::certs(&mut fs::read(cert)?.as_slice())
+				.collect::<Result<Vec<_>, _>>()?;
+			let key = rustls_pemfile::private_key(&mut fs::read(key)?.as_slice())?
+				.ok_or("No private key found in client key file")?;
+			builder.with_client_auth_cert(certs, key)?
+		},
+		_ => builder.with_no_client_auth(),
+	};
+	Ok(tokio_postgres_rustls::MakeRustlsConnect::new(tls))
+}
+
+/// Without a TLS feature only unencrypted connections are possible.
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+fn make_tls(config: &TlsConfig) -> Result<Tls, Box<dyn Error>> {
+	if config.ca_cert.is_some() || config.client_cert.is_some() || config.client_key.is_some() {
+		return Err("Certificates need a build with the native-tls or rustls feature".into());
+	}
+	Ok(r2d2_postgres::postgres::NoTls)
+}
+
+pub fn connect(uri: &str, tls: &TlsConfig) -> Result<ClientPool, Box<dyn Error>> {
+	let manager = PostgresConnectionManager::new(uri.parse()?, make_tls(tls)?);
 	let pool = Pool::new(manager)?;
 	let schema = include_str!("../scrap.sql");
 	let mut client = pool.get()?;
 	client.simple_query(schema)?;
 	Ok(pool)
 }
+
+/// Deletes sessions past the idle or absolute timeout configured in `scrap.ctf`.
+pub fn expire_sessions(pool: &ClientPool) -> Result<u64, Box<dyn Error>> {
+	let mut client = pool.get()?;
+	Ok(client.execute("DELETE FROM scrap.session WHERE expired(created, seen)", &[])?)
+}
+
+/// Deletes password reset links that were never used.
+pub fn expire_resets(pool: &ClientPool) -> Result<u64, Box<dyn Error>> {
+	let mut client = pool.get()?;
+	Ok(client.execute("DELETE FROM scrap.reset WHERE expires < NOW()", &[])?)
+}
+
+/// Deletes two-factor logins that were never completed.
+pub fn expire_logins(pool: &ClientPool) -> Result<u64, Box<dyn Error>> {
+	let mut client = pool.get()?;
+	Ok(client.execute("DELETE FROM scrap.pending WHERE created < NOW() - INTERVAL '5 minutes'", &[])?)
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+use std::sync::OnceLock;
+use std::time::Duration;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+
+static OAUTH: OnceLock<OAuth> = OnceLock::new();
+
+/// Enables Discord login for the lifetime of the process.
+pub fn configure(oauth: OAuth) {
+	let _ = OAUTH.set(oauth);
+}
+
+pub fn oauth() -> Option<&'static OAuth> {
+	OAUTH.get()
+}
+
+/// Discord OAuth2 application used to verify which Discord account a member owns.
+pub struct OAuth {
+	pub client_id: String,
+	pub client_secret: String,
+	pub redirect_uri: String,
+	/// Base URL of the provider, `https://discord.com` outside of testing.
+	pub base: String,
+}
+
+#[derive(Deserialize)]
+struct Token {
+	access_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct User {
+	pub id: String,
+	pub username: String,
+	pub discriminator: Option<String>,
+}
+
+impl User {
+	/// Handle in the form members typed before OAuth, keeping legacy discriminators.
+	pub fn handle(&self) -> String {
+		match self.discriminator.as_deref() {
+			Some(discriminator) if discriminator != "0" => format!("{}#{}", self.username, discriminator),
+			_ => self.username.clone(),
+		}
+	}
+}
+
+impl OAuth {
+	pub fn authorize_url(&self, state: &str) -> String {
+		format!("{}/oauth2/authorize?response_type=code&scope=identify&client_id={}&redirect_uri={}&state={}",
+			self.base,
+			utf8_percent_encode(&self.client_id, NON_ALPHANUMERIC),
+			utf8_percent_encode(&self.redirect_uri, NON_ALPHANUMERIC),
+			utf8_percent_encode(state, NON_ALPHANUMERIC))
+	}
+
+	/// Exchanges an authorization code for the Discord user that granted it.
+	pub fn identify(&self, code: &str) -> Result<User, Box<dyn Error>> {
+		// The member's login request waits on Discord, so a slow provider must not hang it.
+		let agent = ureq::AgentBuilder::new()
+			.timeout_connect(Duration::from_secs(5))
+			.timeout(Duration::from_secs(10))
+			.build();
+		let token: Token = agent.post(&format!("{}/api/oauth2/token", self.base))
+			.send_form(&[
+				("client_id", &self.client_id),
+				("client_secret", &self.client_secret),
+				("grant_type", "authorization_code"),
+				("code", code),
+				("redirect_uri", &self.redirect_uri),
+			])?
+			.into_json()?;
+		Ok(agent.get(&format!("{}/api/users/@me", self.base))
+			.set("authorization", &format!("Bearer {}", token.access_token))
+			.call()?
+			.into_json()?)
+	}
+}
@@ -0,0 +1,37 @@
+use std::io;
+use std::sync::Mutex;
+use futures::Stream;
+use futures::sync::mpsc::{unbounded, UnboundedSender};
+use serde::Serialize;
+use warp::sse::ServerSentEvent;
+
+#[derive(Clone)]
+struct Event {
+	name: &'static str,
+	data: String,
+}
+
+/// Fans events out to every browser connected to `/live`.
+#[derive(Default)]
+pub struct Hub {
+	clients: Mutex<Vec<UnboundedSender<Event>>>,
+}
+
+impl Hub {
+	/// Sends an event to every connected browser, forgetting those that have gone away.
+	pub fn publish<T: Serialize>(&self, name: &'static str, data: &T) {
+		let event = match serde_json::to_string(data) {
+			Ok(data) => Event { name, data },
+			Err(e) => return eprintln!("Failed to serialize {} event: {}", name, e),
+		};
+		self.clients.lock().unwrap().retain(|client| client.unbounded_send(event.clone()).is_ok());
+	}
+
+	pub fn subscribe(&self) -> impl Stream<Item = impl ServerSentEvent, Error = io::Error> {
+		let (sender, receiver) = unbounded();
+		self.clients.lock().unwrap().push(sender);
+		receiver
+			.map(|event| (warp::sse::event(event.name), warp::sse::data(event.data)))
+			.map_err(|()| io::Error::new(io::ErrorKind::BrokenPipe, "Hub closed"))
+	}
+}
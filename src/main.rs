@@ -1,14 +1,22 @@
 #![feature(proc_macro_hygiene)]
+#![recursion_limit = "256"]
 
 use std::thread;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use clap::{App, Arg, value_t};
 use signal_hook::SIGUSR1;
 use signal_hook::iterator::Signals;
 
 mod database;
+mod discord;
+mod live;
+mod notify;
 mod repository;
 mod server;
+mod totp;
+mod webhook;
 
 fn main() {
 	let matches = App::new("scrap").version("1.0")
@@ -32,21 +40,101 @@ fn main() {
 			.help("PostgreSQL database URI")
 			.takes_value(true)
 			.required(true))
+		.arg(Arg::with_name("base-url")
+			.long("base-url")
+			.help("Public URL of the platform used in links sent outside of it, eg. https://cyber.uclaacm.com")
+			.takes_value(true)
+			.required(true))
+		.arg(Arg::with_name("ca-cert")
+			.long("ca-cert")
+			.help("PEM root certificate for verifying the database server")
+			.takes_value(true))
+		.arg(Arg::with_name("client-cert")
+			.long("client-cert")
+			.help("PEM client certificate for database authentication")
+			.takes_value(true)
+			.requires("client-key"))
+		.arg(Arg::with_name("client-key")
+			.long("client-key")
+			.help("PEM (PKCS#8) client private key for database authentication")
+			.takes_value(true)
+			.requires("client-cert"))
+		.arg(Arg::with_name("discord-client-id")
+			.long("discord-client-id")
+			.help("Discord OAuth2 client ID, enables logging in with Discord")
+			.takes_value(true)
+			.requires_all(&["discord-client-secret", "discord-redirect"]))
+		.arg(Arg::with_name("discord-client-secret")
+			.long("discord-client-secret")
+			.help("Discord OAuth2 client secret")
+			.takes_value(true))
+		.arg(Arg::with_name("discord-redirect")
+			.long("discord-redirect")
+			.help("Discord OAuth2 redirect URI, eg. https://cyber.uclaacm.com/login/discord/callback")
+			.takes_value(true))
+		.arg(Arg::with_name("discord-base")
+			.long("discord-base")
+			.help("Discord OAuth2 provider base URL")
+			.takes_value(true)
+			.default_value("https://discord.com"))
+		.arg(Arg::with_name("webhook")
+			.long("webhook")
+			.help("URL to POST solve, first blood, registration and start/stop events to as JSON")
+			.takes_value(true)
+			.multiple(true)
+			.number_of_values(1))
+		.arg(Arg::with_name("discord-webhook")
+			.long("discord-webhook")
+			.help("Discord webhook URL to post solve, first blood, registration and start/stop messages to")
+			.takes_value(true)
+			.multiple(true)
+			.number_of_values(1))
+		.arg(Arg::with_name("notifier")
+			.long("notifier")
+			.help("Program run with a username, Discord handle and message to deliver password reset links")
+			.takes_value(true))
 		.get_matches();
 	let port = value_t!(matches.value_of("port"), u16).unwrap();
 	let uri = matches.value_of("uri").unwrap().to_owned();
 	let repo_str = matches.value_of("repo").unwrap().to_owned();
 	let static_str = matches.value_of("static").unwrap().to_owned();
+	let base_url = matches.value_of("base-url").unwrap().trim_end_matches('/').to_owned();
+	let tls = database::TlsConfig {
+		ca_cert: matches.value_of("ca-cert").map(PathBuf::from),
+		client_cert: matches.value_of("client-cert").map(PathBuf::from),
+		client_key: matches.value_of("client-key").map(PathBuf::from),
+	};
+
+	if let Some(client_id) = matches.value_of("discord-client-id") {
+		discord::configure(discord::OAuth {
+			client_id: client_id.to_owned(),
+			client_secret: matches.value_of("discord-client-secret").unwrap().to_owned(),
+			redirect_uri: matches.value_of("discord-redirect").unwrap().to_owned(),
+			base: matches.value_of("discord-base").unwrap().trim_end_matches('/').to_owned(),
+		});
+	}
+	let notifier = matches.value_of("notifier")
+		.map(|program| Arc::new(notify::Command::new(PathBuf::from(program))) as Arc<dyn notify::Notifier>);
 
-	let pool = database::connect(&uri)
+	let mut targets = Vec::new();
+	for url in matches.values_of("webhook").into_iter().flatten() {
+		targets.push(webhook::Target { url: url.to_owned(), format: webhook::Format::Json });
+	}
+	for url in matches.values_of("discord-webhook").into_iter().flatten() {
+		targets.push(webhook::Target { url: url.to_owned(), format: webhook::Format::Discord });
+	}
+	let webhooks = Arc::new(webhook::Webhooks::new(targets));
+
+	let pool = database::connect(&uri, &tls)
 		.expect("Failed to connect to database");
 	repository::load(&Path::new(&repo_str), &Path::new(&static_str), &pool)
 		.expect("Failed to load repository");
 
+	let static_path = PathBuf::from(&static_str);
 	let signals = Signals::new(&[SIGUSR1]).unwrap();
 	thread::spawn(move || {
 		for _signal in signals.forever() {
-			let pool = match database::connect(&uri) {
+			let pool = match database::connect(&uri, &tls) {
 				Ok(pool) => pool,
 				Err(e) => {
 					eprintln!("Failed to connect to database on signal: {}", e);
@@ -63,5 +151,28 @@ fn main() {
 		}
 	});
 
-	server::run(port, pool);
+	let cleanup_pool = pool.clone();
+	thread::spawn(move || loop {
+		if let Err(e) = database::expire_sessions(&cleanup_pool) {
+			eprintln!("Failed to expire sessions: {}", e);
+		}
+		if let Err(e) = database::expire_resets(&cleanup_pool) {
+			eprintln!("Failed to expire password resets: {}", e);
+		}
+		if let Err(e) = database::expire_logins(&cleanup_pool) {
+			eprintln!("Failed to expire two-factor logins: {}", e);
+		}
+		thread::sleep(Duration::from_secs(3600));
+	});
+
+	let schedule_pool = pool.clone();
+	let schedule_webhooks = webhooks.clone();
+	thread::spawn(move || loop {
+		if let Err(e) = webhook::check_schedule(&schedule_pool, &schedule_webhooks) {
+			eprintln!("Failed to check CTF schedule: {}", e);
+		}
+		thread::sleep(Duration::from_secs(30));
+	});
+
+	server::run(port, pool, static_path, base_url, notifier, webhooks);
 }
@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the notifier may run before it is killed, since the admin's request waits on it.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delivers a message to a member outside the platform, eg. a password reset link.
+pub trait Notifier: Send + Sync {
+	fn notify(&self, name: &str, discord: &str, message: &str) -> Result<(), Box<dyn Error>>;
+}
+
+/// Runs an external program with the member's username, Discord handle and the message as arguments.
+pub struct Command {
+	program: PathBuf,
+}
+
+impl Command {
+	pub fn new(program: PathBuf) -> Command {
+		Command { program }
+	}
+}
+
+impl Notifier for Command {
+	fn notify(&self, name: &str, discord: &str, message: &str) -> Result<(), Box<dyn Error>> {
+		let mut child = process::Command::new(&self.program)
+			.arg(name)
+			.arg(discord)
+			.arg(message)
+			.stdin(process::Stdio::null())
+			.spawn()?;
+		let deadline = Instant::now() + TIMEOUT;
+		let status = loop {
+			if let Some(status) = child.try_wait()? {
+				break status;
+			}
+			if Instant::now() >= deadline {
+				let _ = child.kill();
+				let _ = child.wait();
+				return Err(format!("Notifier did not finish within {} seconds", TIMEOUT.as_secs()).into());
+			}
+			thread::sleep(Duration::from_millis(50));
+		};
+		if !status.success() {
+			return Err(format!("Notifier exited with {}", status).into());
+		}
+		Ok(())
+	}
+}
@@ -6,6 +6,8 @@ use std::path::Path;
 use chrono::DateTime;
 use pulldown_cmark::{CowStr, Event, LinkType, Parser, Tag};
 use pulldown_cmark::html::push_html;
+use r2d2_postgres::postgres::Transaction;
+use regex::Regex;
 use serde::Deserialize;
 use tiny_keccak::sha3_256;
 
@@ -13,10 +15,110 @@ use crate::database::ClientPool;
 
 #[derive(Deserialize)]
 struct Ctf {
+	/// Names this CTF in the archive; a new slug archives the previous CTF and starts a fresh scoreboard.
+	slug: Option<String>,
 	title: String,
 	home: String,
 	start: Option<toml::value::Datetime>,
 	stop: Option<toml::value::Datetime>,
+	session_idle: Option<String>,
+	session_lifetime: Option<String>,
+	team_size: Option<i32>,
+	require_admin_totp: Option<bool>,
+	#[serde(default)]
+	divisions: Vec<Division>,
+	#[serde(default)]
+	rewards: Rewards,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct Rewards {
+	ticket_points: i32,
+	prizes: Vec<Prize>,
+	grants: Vec<Grant>,
+}
+
+/// A regular prize. One-time prizes keep their share of the total weight until a team wins them.
+#[derive(Deserialize)]
+struct Prize {
+	name: String,
+	weight: f64,
+	#[serde(default)]
+	one_time: bool,
+}
+
+/// Premium tickets granted for a writeup of a challenge worth at most `max_value` points.
+#[derive(Deserialize)]
+struct Grant {
+	max_value: Option<i32>,
+	tickets: i32,
+}
+
+impl Default for Rewards {
+	fn default() -> Rewards {
+		let prize = |name: &str, weight, one_time| Prize { name: name.to_string(), weight, one_time };
+		Rewards {
+			ticket_points: 50,
+			prizes: vec![
+				prize("Zoom Background", 39.9, false),
+				prize("Profile Picture", 26.6, false),
+				prize("Cyber Stickers", 2.0, true),
+				prize("Cyber Discord Role", 30.0, true),
+				prize("Cyber Discord Emote", 0.5, true),
+				prize("Cyber Serenade", 0.5, true),
+				prize("Steam Game", 0.5, true),
+			],
+			grants: vec![
+				Grant { max_value: Some(20), tickets: 1 },
+				Grant { max_value: Some(40), tickets: 2 },
+				Grant { max_value: None, tickets: 3 },
+			],
+		}
+	}
+}
+
+impl Rewards {
+	fn validate(&self) -> Result<(), String> {
+		if self.ticket_points <= 0 {
+			return Err("rewards.ticket_points must be positive".to_string());
+		}
+		let mut names = HashSet::new();
+		for prize in &self.prizes {
+			if prize.name.trim().is_empty() {
+				return Err("Reward prizes need a name".to_string());
+			}
+			if !names.insert(&prize.name) {
+				return Err(format!("Reward prize {} is listed twice", prize.name));
+			}
+			if !prize.weight.is_finite() || prize.weight < 0.0 {
+				return Err(format!("Reward prize {} needs a weight of at least 0", prize.name));
+			}
+		}
+		if !self.prizes.iter().any(|prize| !prize.one_time && prize.weight > 0.0) {
+			return Err("Rewards need a prize that is not one_time with a positive weight".to_string());
+		}
+		for (position, grant) in self.grants.iter().enumerate() {
+			if grant.tickets <= 0 {
+				return Err("Premium ticket grants must give at least one ticket".to_string());
+			}
+			match (grant.max_value, self.grants.get(position + 1)) {
+				(None, Some(_)) => return Err("Only the last premium ticket grant may leave out max_value".to_string()),
+				(Some(max_value), Some(Grant { max_value: Some(next), .. })) if *next <= max_value =>
+					return Err("Premium ticket grants must be listed by increasing max_value".to_string()),
+				_ => (),
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Deserialize)]
+struct Division {
+	slug: String,
+	name: String,
+	email_domain: Option<String>,
+	approval: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -24,10 +126,16 @@ struct EventInfo {
 	id: i32,
 	title: String,
 	short: String, 
-	date: String,
+	start: toml::value::Datetime,
+	stop: Option<toml::value::Datetime>,
+	location: Option<String>,
+	quarter: Option<String>,
 	description: String,
 	link: String,
 	slides: String,
+	recording: Option<String>,
+	#[serde(default)]
+	files: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -41,6 +149,63 @@ struct Challenge {
 	files: Vec<String>,
 	flag: String,
 	enabled: bool,
+	/// Slug of the event the challenge was used in.
+	event: Option<String>,
+	/// Heading the challenge is grouped under on the challenges page.
+	category: Option<String>,
+	difficulty: Option<String>,
+	/// Markdown hints shown on the challenge's page.
+	#[serde(default)]
+	hints: Vec<String>,
+}
+
+/// Copies files into content-hashed directories under `static/files`, returning each file's name and URL.
+fn copy_files(dir: &Path, files: &[String], static_files_path: &Path, hashes: &mut HashSet<OsString>) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+	let mut urls = Vec::new();
+	for file_path in files.iter()
+		.map(|path| dir.join(path)) {
+		let hash = hex::encode(&sha3_256(&fs::read(&file_path)?)[..8]);
+		let hash_path = static_files_path.join(&hash);
+		let file_name = String::from(file_path.file_name().unwrap().to_string_lossy());
+		let url = format!("/static/files/{}/{}", hash, &file_name);
+		fs::create_dir_all(&hash_path)?;
+		fs::copy(&file_path, hash_path.join(&file_name))?;
+		urls.push((file_name, url));
+		hashes.insert(OsString::from(hash));
+	}
+	Ok(urls)
+}
+
+/// Renders Markdown, pointing links to copied files at their URLs.
+fn render(markdown: &str, urls: &[(String, String)]) -> String {
+	let url_map: HashMap<&String, &String> = urls.iter().map(|(file_name, url)| (file_name, url)).collect();
+	let parser = Parser::new(markdown)
+		.map(|event| match event {
+			Event::Start(Tag::Link(LinkType::Inline, href, title)) => {
+				match url_map.get(&href.to_string()) {
+					Some(url) => Event::Start(Tag::Link(LinkType::Inline, CowStr::Borrowed(url), title)),
+					None => Event::Start(Tag::Link(LinkType::Inline, href, title)),
+				}
+			},
+			_ => event,
+		});
+	let mut html = String::new();
+	push_html(&mut html, parser);
+	html
+}
+
+/// Freezes the current CTF's final standings and archives it. Its challenges, solves and announcements
+/// stay with it; teams and members carry over into the next CTF starting from zero.
+fn archive(transaction: &mut Transaction) -> Result<(), Box<dyn Error>> {
+	transaction.execute("INSERT INTO scrap.standing (ctf, place, team, name, score, solves, division)
+		SELECT current_ctf(), ROW_NUMBER() OVER (ORDER BY team.score DESC, team.submit ASC), team.id, team.name, team.score,
+		(SELECT COUNT(*) FROM scrap.solve solve WHERE solve.team=team.id AND solve.ctf=current_ctf()), team_division(team.id)
+		FROM scrap.team team
+		WHERE NOT team.hidden AND NOT team.banned",
+		&[])?;
+	transaction.batch_execute("UPDATE scrap.team SET score=0, redeemed_score=0, submit=NOW();
+		UPDATE scrap.ctf SET current=false, archived=NOW() WHERE current;")?;
+	Ok(())
 }
 
 pub fn load(repo_path: &Path, static_path: &Path, pool: &ClientPool) -> Result<(), Box<dyn Error>> {
@@ -48,23 +213,95 @@ pub fn load(repo_path: &Path, static_path: &Path, pool: &ClientPool) -> Result<(
 
 	let config = fs::read_to_string(repo_path.join("ctf.toml"))?;
 	let ctf: Ctf = toml::from_str(&config)?;
+	ctf.rewards.validate()?;
+	// Email addresses are never verified, so only an admin can vouch for a member's domain.
+	if let Some(division) = ctf.divisions.iter().find(|division| division.email_domain.is_some() && division.approval == Some(false)) {
+		return Err(format!("Division {} has an email_domain, so it cannot set approval = false", division.slug).into());
+	}
 	let parser = Parser::new(&ctf.home);
 	let mut home = String::new();
 	push_html(&mut home, parser);
-	client.simple_query("SELECT setval(pg_get_serial_sequence('scrap.challenge', 'id'), max(id)) FROM scrap.challenge")?;
-	client.execute("INSERT INTO scrap.ctf (title, home, start, stop) VALUES ($1, $2, $3, $4)
-		ON CONFLICT (id) DO UPDATE SET title=$1, home=$2, start=$3, stop=$4",
+	// Everything below, including archiving the previous CTF, is rolled back if any part of the repository fails to load.
+	let mut transaction = client.transaction()?;
+	let previous: Option<String> = transaction.query_opt("SELECT slug FROM scrap.ctf WHERE current", &[])?
+		.map(|ctf| ctf.get("slug"));
+	let ctf_slug = ctf.slug.clone().or_else(|| previous.clone()).unwrap_or_else(|| "ctf".to_string());
+	if previous.is_some() && previous.as_ref() != Some(&ctf_slug) {
+		if transaction.query_opt("SELECT FROM scrap.ctf WHERE slug=$1", &[&ctf_slug])?.is_some() {
+			return Err(format!("CTF {} is already archived, choose a new slug", ctf_slug).into());
+		}
+		archive(&mut transaction)?;
+	}
+	transaction.execute("INSERT INTO scrap.ctf (title, home, start, stop, session_idle, session_lifetime, team_size, require_admin_totp, ticket_points,
+		slug, current)
+		VALUES ($1, $2, $3, $4, COALESCE($5::TEXT, '1 day')::INTERVAL, COALESCE($6::TEXT, '30 days')::INTERVAL, $7, COALESCE($8, false), $9,
+		$10, true)
+		ON CONFLICT (slug) DO UPDATE SET title=$1, home=$2, start=$3, stop=$4,
+		session_idle=EXCLUDED.session_idle, session_lifetime=EXCLUDED.session_lifetime, team_size=$7,
+		require_admin_totp=EXCLUDED.require_admin_totp, ticket_points=$9",
 		&[
 			&ctf.title,
 			&home,
 			&ctf.start.map(|start| DateTime::parse_from_rfc3339(&start.to_string()).unwrap()),
 			&ctf.stop.map(|stop| DateTime::parse_from_rfc3339(&stop.to_string()).unwrap()),
+			&ctf.session_idle,
+			&ctf.session_lifetime,
+			&ctf.team_size,
+			&ctf.require_admin_totp,
+			&ctf.rewards.ticket_points,
+			&ctf_slug,
 		]
 	)?;
 
+	transaction.execute("DELETE FROM scrap.catalog", &[])?;
+	for (position, prize) in ctf.rewards.prizes.iter().enumerate() {
+		transaction.execute("INSERT INTO scrap.catalog (name, weight, one_time, position)
+			VALUES ($1, $2, $3, $4)",
+			&[
+				&prize.name,
+				&prize.weight,
+				&prize.one_time,
+				&(position as i32),
+			]
+		)?;
+	}
+	transaction.execute("DELETE FROM scrap.grant", &[])?;
+	for (position, grant) in ctf.rewards.grants.iter().enumerate() {
+		transaction.execute("INSERT INTO scrap.grant (position, max_value, tickets)
+			VALUES ($1, $2, $3)",
+			&[
+				&(position as i32),
+				&grant.max_value,
+				&grant.tickets,
+			]
+		)?;
+	}
+
+	for (position, division) in ctf.divisions.iter().enumerate() {
+		transaction.execute("INSERT INTO scrap.division (slug, name, email_domain, approval, position)
+			VALUES ($1, $2, $3, COALESCE($4, $3::TEXT IS NOT NULL), $5)
+			ON CONFLICT (slug) DO UPDATE SET name=$2, email_domain=$3, approval=EXCLUDED.approval, position=$5",
+			&[
+				&division.slug,
+				&division.name,
+				&division.email_domain,
+				&division.approval,
+				&(position as i32),
+			]
+		)?;
+	}
+	let slugs: Vec<&String> = ctf.divisions.iter().map(|division| &division.slug).collect();
+	transaction.execute("DELETE FROM scrap.division WHERE NOT (slug = ANY($1))", &[&slugs])?;
+
+	let mut hashes = HashSet::new();
+	let static_files_path = static_path.join("files");
+	fs::create_dir_all(&static_files_path)?;
 	let static_events_path = static_path.join("events");
 	fs::create_dir_all(&static_events_path)?;
 
+	// Slugs are checked before anything is written so clashes are reported by name.
+	let mut events = Vec::new();
+	let mut event_slugs = HashMap::new();
 	for event_path in fs::read_dir(repo_path.join("events"))?
 		.filter_map(|entry| entry.ok())
 		.map(|entry| entry.path())
@@ -72,31 +309,57 @@ pub fn load(repo_path: &Path, static_path: &Path, pool: &ClientPool) -> Result<(
 		
 		let config = fs::read_to_string(event_path.join("event.toml"))?;
 		let event: EventInfo = toml::from_str(&config)?;
+		let slug = event.short.replace(" ", "-").to_lowercase();
+		if let Some(other) = event_slugs.insert(slug.clone(), event.id) {
+			return Err(format!("Events {} and {} both have the slug {}", other, event.id, slug).into());
+		}
+		events.push((event_path, event, slug));
+	}
 
-		let mut description = String::new();
-		push_html(&mut description, Parser::new(&event.description));
+	let event_ids: Vec<i32> = events.iter().map(|(_, event, _)| event.id).collect();
+	transaction.execute("DELETE FROM scrap.event WHERE NOT (id = ANY($1))", &[&event_ids])?;
+	for (event_path, event, slug) in events {
+		let files = copy_files(&event_path, &event.files, &static_files_path, &mut hashes)?;
+		let description = render(&event.description, &files);
+		let urls: Vec<&String> = files.iter().map(|(_, url)| url).collect();
 
-		let icon_file = format!("{}.svg", event.short.replace(" ", "-").to_lowercase());
+		let icon_file = format!("{}.svg", slug);
 		fs::copy(event_path.join("icon.svg"), static_events_path.join(&icon_file))?;
 
-		client.execute("INSERT INTO scrap.event (id, title, short, date, description, link, slides) VALUES ($1, $2, $3, $4, $5, $6, $7)
-			ON CONFLICT (id) DO UPDATE SET title=$2, short=$3, date=$4, description=$5, link=$6, slides=$7",
+		// Times are shown in the offset they were written with, eg. 2023-01-10T18:00:00-08:00.
+		let start = DateTime::parse_from_rfc3339(&event.start.to_string())
+			.map_err(|e| format!("Event {} needs a start with an offset: {}", event.id, e))?;
+		let stop = match &event.stop {
+			Some(stop) => Some(DateTime::parse_from_rfc3339(&stop.to_string())
+				.map_err(|e| format!("Event {} needs a stop with an offset: {}", event.id, e))?),
+			None => None,
+		};
+
+		transaction.execute("INSERT INTO scrap.event (id, title, short, start, stop, utc_offset, location, quarter, description, link, slides,
+			slug, recording, files)
+			VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+			ON CONFLICT (id) DO UPDATE SET title=$2, short=$3, start=$4, stop=$5, utc_offset=$6, location=$7, quarter=$8,
+			description=$9, link=$10, slides=$11, slug=$12, recording=$13, files=$14",
 			&[
 				&event.id,
 				&event.title,
 				&event.short,
-				&event.date,
+				&start,
+				&stop,
+				&start.offset().local_minus_utc(),
+				&event.location,
+				&event.quarter,
 				&description,
 				&event.link,
 				&event.slides,
+				&slug,
+				&event.recording,
+				&urls,
 			]
 		)?;
 	}
 	
-	let mut hashes = HashSet::new();
-	let static_files_path = static_path.join("files");
-	fs::create_dir_all(&static_files_path)?;
-	client.simple_query("UPDATE scrap.challenge SET enabled=NULL")?;
+	transaction.execute("UPDATE scrap.challenge SET enabled=NULL WHERE ctf=$1", &[&ctf_slug])?;
 	for challenge_path in fs::read_dir(repo_path.join("challenges"))?
 		.filter_map(|entry| entry.ok())
 		.map(|entry| entry.path())
@@ -104,38 +367,24 @@ pub fn load(repo_path: &Path, static_path: &Path, pool: &ClientPool) -> Result<(
 
 		let config = fs::read_to_string(challenge_path.join("challenge.toml"))?;
 		let challenge: Challenge = toml::from_str(&config)?;
-
-		let mut url_map = HashMap::new();
-		for file_path in challenge.files.iter()
-			.map(|path| challenge_path.join(path)) {
-			let hash = hex::encode(&sha3_256(&fs::read(&file_path)?)[..8]);
-			let hash_path = static_files_path.join(&hash);
-			let file_name = String::from(file_path.file_name().unwrap().to_string_lossy());
-			let url = format!("/static/files/{}/{}", hash, &file_name);
-			fs::create_dir_all(&hash_path)?;
-			fs::copy(&file_path, hash_path.join(&file_name))?;
-			url_map.insert(file_name, url);
-			hashes.insert(OsString::from(hash));
+		if let Some(event) = &challenge.event {
+			if !event_slugs.contains_key(event) {
+				return Err(format!("Challenge {} is from unknown event {}", challenge.slug, event).into());
+			}
 		}
 
-		let parser = Parser::new(&challenge.description)
-			.map(|event| match event {
-				Event::Start(Tag::Link(LinkType::Inline, href, title)) => {
-					match url_map.get(&href.to_string()) {
-						Some(url) => Event::Start(Tag::Link(LinkType::Inline, CowStr::Borrowed(url), title)),
-						None => Event::Start(Tag::Link(LinkType::Inline, href, title)),
-					}
-				},
-				_ => event,
-			});
-		let mut description = String::new();
-		push_html(&mut description, parser);
-
-		client.execute(
-		"INSERT INTO scrap.challenge (slug, title, author, value, description, tags, flag, enabled)
-		VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-		ON CONFLICT (slug) DO UPDATE
-		SET title=$2, author=$3, value=$4, description=$5, tags=$6, flag=$7, enabled=$8",
+		let files = copy_files(&challenge_path, &challenge.files, &static_files_path, &mut hashes)?;
+		let description = render(&challenge.description, &files);
+		let urls: Vec<&String> = files.iter().map(|(_, url)| url).collect();
+		let hints: Vec<String> = challenge.hints.iter().map(|hint| render(hint, &files)).collect();
+
+		transaction.execute(
+		"INSERT INTO scrap.challenge (slug, title, author, value, description, tags, flag, enabled, event, category, difficulty,
+			files, hints, ctf)
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+		ON CONFLICT (ctf, slug) DO UPDATE
+		SET title=$2, author=$3, value=$4, description=$5, tags=$6, flag=$7, enabled=$8, event=$9, category=$10, difficulty=$11,
+			files=$12, hints=$13",
 		&[
 			&challenge.slug,
 			&challenge.title,
@@ -145,9 +394,40 @@ pub fn load(repo_path: &Path, static_path: &Path, pool: &ClientPool) -> Result<(
 			&challenge.tags,
 			&challenge.flag,
 			&challenge.enabled,
+			&challenge.event,
+			&challenge.category,
+			&challenge.difficulty,
+			&urls,
+			&hints,
+			&ctf_slug,
 		])?;
 	}
-	client.simple_query("DELETE FROM scrap.challenge WHERE enabled IS NULL")?;
+	transaction.execute("DELETE FROM scrap.challenge WHERE ctf=$1 AND enabled IS NULL", &[&ctf_slug])?;
+	// Solves of removed challenges go with them, so scores are recomputed from what is left.
+	transaction.execute("UPDATE scrap.team team
+		SET score=COALESCE((SELECT SUM(challenge.value)
+		FROM scrap.solve solve
+		JOIN scrap.challenge challenge ON challenge.id=solve.challenge
+		WHERE solve.team=team.id AND solve.ctf=$1), 0)
+		+ COALESCE((SELECT SUM(adjustment.delta)
+		FROM scrap.adjustment adjustment
+		WHERE adjustment.team=team.id AND adjustment.ctf=$1), 0)",
+		&[&ctf_slug])?;
+	// Archived challenges are no longer in the repository but still link to their files.
+	let file_url = Regex::new(r"/static/files/([0-9a-f]+)/").unwrap();
+	for challenge in transaction.query("SELECT description, COALESCE(files, '{}') AS files, COALESCE(hints, '{}') AS hints
+		FROM scrap.challenge WHERE ctf<>$1", &[&ctf_slug])? {
+		let description: String = challenge.get("description");
+		let files: Vec<String> = challenge.get("files");
+		let hints: Vec<String> = challenge.get("hints");
+		for text in std::iter::once(&description).chain(files.iter()).chain(hints.iter()) {
+			for captures in file_url.captures_iter(text) {
+				hashes.insert(OsString::from(&captures[1]));
+			}
+		}
+	}
+	transaction.commit()?;
+
 	for hash_path in fs::read_dir(static_files_path)?
 		.filter_map(|entry| entry.ok())
 		.filter(|entry| !hashes.contains(&entry.file_name()))
@@ -159,3 +439,43 @@ pub fn load(repo_path: &Path, static_path: &Path, pool: &ClientPool) -> Result<(
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rewards(grants: &[(Option<i32>, i32)]) -> Rewards {
+		Rewards {
+			grants: grants.iter().map(|&(max_value, tickets)| Grant { max_value, tickets }).collect(),
+			..Rewards::default()
+		}
+	}
+
+	#[test]
+	fn default_rewards_are_valid() {
+		assert_eq!(Rewards::default().validate(), Ok(()));
+	}
+
+	#[test]
+	fn grants_must_increase_by_max_value() {
+		assert_eq!(rewards(&[(Some(20), 1), (Some(40), 2), (Some(60), 3)]).validate(), Ok(()));
+		assert_eq!(rewards(&[(Some(40), 1), (Some(20), 2)]).validate(),
+			Err("Premium ticket grants must be listed by increasing max_value".to_string()));
+		assert_eq!(rewards(&[(Some(20), 1), (Some(20), 2)]).validate(),
+			Err("Premium ticket grants must be listed by increasing max_value".to_string()));
+	}
+
+	#[test]
+	fn only_the_last_grant_may_leave_out_max_value() {
+		assert_eq!(rewards(&[(Some(20), 1), (None, 2)]).validate(), Ok(()));
+		assert_eq!(rewards(&[(None, 3)]).validate(), Ok(()));
+		assert_eq!(rewards(&[(None, 1), (Some(20), 2)]).validate(),
+			Err("Only the last premium ticket grant may leave out max_value".to_string()));
+	}
+
+	#[test]
+	fn grants_must_give_tickets() {
+		assert_eq!(rewards(&[(Some(20), 0)]).validate(),
+			Err("Premium ticket grants must give at least one ticket".to_string()));
+	}
+}
@@ -1,17 +1,37 @@
 
 use std::collections::HashMap;
-use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use chrono::{DateTime, FixedOffset, Utc};
 use maud::{html, DOCTYPE, Markup, PreEscaped};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
 use r2d2_postgres::postgres::error::SqlState;
 use r2d2_postgres::postgres::row::Row;
+use r2d2_postgres::postgres::Transaction;
 use warp::{any, body, reply, Filter, Reply, Rejection};
+use warp::fs::File;
 use warp::http::{Response, StatusCode};
+use warp::http::header::HeaderValue;
 use warp::reject::custom;
 use warp::reply::with_header;
-use warp::path::{end, path};
+use warp::path::{end, param, path, peek, Peek};
 use regex::Regex;
+use serde::Serialize;
 
 use crate::database::{Client, ClientPool};
+use crate::discord;
+use crate::live::Hub;
+use crate::notify::Notifier;
+use crate::totp;
+use crate::webhook::{Event, Webhooks};
+
+/// Number of teams shown on the projector view; `/static/projector.js` shows as many.
+const PROJECTOR_TEAMS: usize = 20;
+
+/// Prefix of API tokens, which are never accepted in place of the session cookie.
+const TOKEN_PREFIX: &str = "scrap_";
 
 macro_rules! result {
 	($expr:expr) => {
@@ -33,11 +53,21 @@ macro_rules! form {
 	}	
 }
 
+/// Accepts both current Discord usernames and legacy `name#1234` handles.
+fn valid_discord(handle: &str) -> bool {
+	Regex::new(r"^([a-z0-9_.]{2,32}|.{2,32}#\d{4})$").unwrap().is_match(handle)
+}
+
 fn make_body(page: &str, content: Markup, mut client: Client, session: String) -> Result<String, Rejection> {
-	let count: i64 = result!(client.query("SELECT COUNT(*) as count FROM scrap.session
-		WHERE cookie=$1",
-		&[&session]))[0].get("count");
-	let title: String = result!(client.query("SELECT title FROM scrap.ctf", &[]))[0].get(0);
+	let count = result!(client.execute("UPDATE scrap.session SET seen=NOW()
+		WHERE cookie=$1 AND NOT expired(created, seen)",
+		&[&session]));
+	let title: String = result!(client.query("SELECT title FROM scrap.ctf WHERE current", &[]))[0].get(0);
+	let announcements = result!(client.query("SELECT message, time FROM scrap.announcement
+		WHERE challenge IS NULL AND ctf=current_ctf() AND time > NOW() - INTERVAL '1 day'
+		ORDER BY time DESC
+		LIMIT 3",
+		&[]));
 	Ok(html! {
 		(DOCTYPE)
 		html {
@@ -68,7 +98,7 @@ fn make_body(page: &str, content: Markup, mut client: Client, session: String) -
 						li { a href="/pbr" {"PBR"} }
 // 						li { a href="/internship" { "Internship" } }
 						@if count > 0 {
-							// li { a href="/rewards" { "Rewards" } }
+							li { a href="/rewards" { "Rewards" } }
 							li { a href="/profile" { "Profile" } }
 							li { a href="/logout" { "Logout" } }
 						} @else {
@@ -77,12 +107,28 @@ fn make_body(page: &str, content: Markup, mut client: Client, session: String) -
 						}
 					}
 				}
-				main { (content) }
+				main {
+					aside id="announcements" class="announcements" {
+						@for announcement in &announcements {
+							(make_announcement(announcement))
+						}
+					}
+					(content)
+				}
+				script src="/static/live.js" {}
 			}
 		}
 	}.into_string())
 }
 
+fn make_announcement(announcement: &Row) -> Markup {
+	let message: String = announcement.get("message");
+	let time: DateTime<Utc> = announcement.get("time");
+	html! {
+		p { strong { (time.format("%Y-%m-%d %H:%M UTC")) } " " (message) }
+	}
+}
+
 fn make_reply(body: String) -> impl Reply {
 	reply::with_header(reply::html(body), "content-security-policy", "script-src 'self' https://ajax.googleapis.com/ajax/libs/jquery/3.5.1/jquery.min.js")
 }
@@ -92,47 +138,49 @@ fn page(title: &str, content: Markup, client: Client, session: String) -> Result
 }
 
 fn get_home(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
-	let home: String = result!(client.query("SELECT home FROM scrap.ctf", &[]))[0].get("home");
+	let home: String = result!(client.query("SELECT home FROM scrap.ctf WHERE current", &[]))[0].get("home");
 	Ok(page("", html! {
 		(PreEscaped(home))
 	}, client, session)?)
 }
 
 fn get_almanac(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+	let now = Utc::now();
 	let events = result!(client.query("SELECT
-		id, title, short, date, description, link, slides, 
-		CASE WHEN id = 1 THEN 1 ELSE 0 END AS is_first,
-		CASE WHEN id % 2 = 0 THEN 1 ELSE 0 END AS is_even
+		id, slug, title, short, start, stop, utc_offset, location, quarter, description, link, slides
 		FROM scrap.event
-		ORDER BY is_first DESC, is_even DESC, id ASC",
+		ORDER BY start ASC, id ASC",
 		&[]));
+	let (upcoming, past): (Vec<&Row>, Vec<&Row>) = events.iter()
+		.partition(|event| event.get::<_, Option<DateTime<Utc>>>("stop").or(event.get("start")).map(|end| end >= now).unwrap_or(true));
+	let mut quarters: Vec<(Option<String>, Vec<&Row>)> = Vec::new();
+	for event in past.into_iter().rev() {
+		let quarter: Option<String> = event.get("quarter");
+		match quarters.last_mut() {
+			Some((last, events)) if *last == quarter => events.push(event),
+			_ => quarters.push((quarter, vec![event])),
+		}
+	}
 	Ok(page("Events", html! {
 		script src="https://ajax.googleapis.com/ajax/libs/jquery/3.5.1/jquery.min.js" {}
-		h1 { "Winter 2023 Events" }
+		h1 { "Events" }
+		p { a href="/events.ics" { "Subscribe to the calendar" } }
+		h2 { "Upcoming" }
+		@if upcoming.is_empty() {
+			p { "No upcoming events." }
+		}
 		section class="events tiles" {
-			
 			ul {
-				@if let Some((first_event, rest_events)) = &events.split_first() {
-					@let first_short: String = first_event.get("short");
-					@let first_slug: String = first_short.replace(" ", "-").to_lowercase();
-					@let first_id: String = format!("{}-deet", first_slug);
+				@for (position, event) in upcoming.iter().enumerate() {
+					@let short: String = event.get("short");
+					@let slug: String = short.replace(" ", "-").to_lowercase();
+					@let id: String = format!("{}-deet", slug);
 					li {
-						input class="workshop" id=(first_slug) name="ws" type="radio" value=(first_id) {}
-						label class="workshop-0" for=(first_slug) { // id gives for, name gives group
-							span {(first_short)}
-							img src= {"/static/events/" (first_slug) ".svg"} alt=(first_short) {}
-						}
-					}
-					@for event in rest_events.iter() {
-						@let short: String = event.get("short");
-						@let slug: String = short.replace(" ", "-").to_lowercase();
-						@let id: String = format!("{}-deet", slug);
-						li {
-							input class="workshop" id=(slug) name="ws" type="radio" value=(id) {}
-							label for=(slug) class="workshop-left" { 
-								span {(short)}
-								img src= {"/static/events/" (slug) ".svg"} alt=(short) {}
-							}
+						input class="workshop" id=(slug) name="ws" type="radio" value=(id) {}
+						// id gives for, name gives group
+						label class=(if position == 0 { "workshop-0" } else { "workshop-left" }) for=(slug) {
+							span {(short)}
+							img src= {"/static/events/" (slug) ".svg"} alt=(short) {}
 						}
 					}
 				}
@@ -141,17 +189,20 @@ fn get_almanac(mut client: Client, session: String) -> Result<impl Reply, Reject
 				}
 			}
 			div class="workshop-deet" id="deet" {
-				@for event in &events {
+				@for event in &upcoming {
 					@let title: String = event.get("title");
 					@let short: String = event.get("short");
 					@let id: String = format!("{}-deet", short.replace(" ", "-").to_lowercase());
 					@let description: String = event.get("description");
-					@let date: String = event.get("date");
+					@let location: Option<String> = event.get("location");
 					@let link: String = event.get("link");
 					@let slides: String = event.get("slides");
 					div class="workshop-description" id=(id) {
 						h1 { (title) }
-						h3 { (date) }
+						h3 { (event_time(event)) }
+						@if let Some(location) = location {
+							h3 { (location) }
+						}
 						@if link == "" {
 							h3 { "Facebook Event Page: Coming Soon!"}
 						} @else {
@@ -163,6 +214,37 @@ fn get_almanac(mut client: Client, session: String) -> Result<impl Reply, Reject
 							h3 { a href={ (slides) } {"Slides"} }
 						}
 						p { (PreEscaped(description)) }
+						p { a href={ "/events/" (event.get::<_, String>("slug")) } { "Event details" } }
+					}
+				}
+			}
+		}
+		@if !quarters.is_empty() {
+			section {
+				h2 { "Past Events" }
+				@for (quarter, events) in &quarters {
+					@if let Some(quarter) = quarter {
+						h3 { (quarter) }
+					}
+					table {
+						tbody {
+							@for event in events {
+								@let title: String = event.get("title");
+								@let slug: String = event.get("slug");
+								@let location: Option<String> = event.get("location");
+								@let slides: String = event.get("slides");
+								tr {
+									td { (event_time(event)) }
+									td { a href={ "/events/" (slug) } { (title) } }
+									td { (location.unwrap_or_default()) }
+									td {
+										@if !slides.is_empty() {
+											a href=(slides) { "Slides" }
+										}
+									}
+								}
+							}
+						}
 					}
 				}
 			}
@@ -171,103 +253,590 @@ fn get_almanac(mut client: Client, session: String) -> Result<impl Reply, Reject
 	}, client, session)?)
 }
 
-fn get_challenges(mut client: Client, session: String, invalid: String) -> Result<impl Reply, Rejection> {
+fn get_event(mut client: Client, session: String, slug: String) -> Result<impl Reply, Rejection> {
+	let event = match result!(client.query("SELECT title, start, stop, utc_offset, location, quarter, description,
+		link, slides, recording, files
+		FROM scrap.event
+		WHERE slug=$1",
+		&[&slug])).pop() {
+		Some(event) => event,
+		None => return Ok(Response::builder()
+			.status(StatusCode::NOT_FOUND)
+			.header("content-security-policy", "script-src 'none'")
+			.body(make_body("Events", html! {
+				h1 { "Events" }
+				p { "No such event." }
+			}, client, session)?)
+			.into_response()),
+	};
+	let challenges = result!(client.query("SELECT challenge.slug, challenge.title, challenge.value,
+		challenge.ctf, ctf.current
+		FROM scrap.challenge challenge
+		JOIN scrap.ctf ctf ON ctf.slug=challenge.ctf
+		WHERE challenge.event=$1 AND challenge.enabled=true
+		ORDER BY challenge.value ASC, challenge.slug ASC",
+		&[&slug]));
+	let title: String = event.get("title");
+	let location: Option<String> = event.get("location");
+	let quarter: Option<String> = event.get("quarter");
+	let description: String = event.get("description");
+	let link: String = event.get("link");
+	let slides: String = event.get("slides");
+	let recording: Option<String> = event.get("recording");
+	let files: Option<Vec<String>> = event.get("files");
+	Ok(page(&title, html! {
+		h1 { (title) }
+		section {
+			h3 { (event_time(&event)) }
+			@if let Some(location) = location {
+				h3 { (location) }
+			}
+			@if let Some(quarter) = quarter {
+				p { (quarter) }
+			}
+			div { (PreEscaped(description)) }
+			ul {
+				@if !link.is_empty() {
+					li { a href=(link) { "Facebook Event Page" } }
+				}
+				@if !slides.is_empty() {
+					li { a href=(slides) { "Slides" } }
+				}
+				@if let Some(recording) = recording {
+					li { a href=(recording) { "Recording" } }
+				}
+			}
+		}
+		@if let Some(files) = files.filter(|files| !files.is_empty()) {
+			section {
+				h2 { "Files" }
+				ul {
+					@for file in &files {
+						li { a href=(file) { (file.rsplit('/').next().unwrap_or(file)) } }
+					}
+				}
+			}
+		}
+		@if !challenges.is_empty() {
+			section {
+				h2 { "Challenges" }
+				ul {
+					@for challenge in &challenges {
+						@let slug: String = challenge.get("slug");
+						@let title: String = challenge.get("title");
+						@let value: i32 = challenge.get("value");
+						@let ctf: String = challenge.get("ctf");
+						@let current: bool = challenge.get("current");
+						@if current {
+							li { a href={ "/challenges/" (slug) } { (title) } " (" (value) " points)" }
+						} @else {
+							li { a href={ "/archive/" (ctf) "#" (slug) } { (title) } " (" (value) " points)" }
+						}
+					}
+				}
+			}
+		}
+		p { a href="/events" { "All events" } }
+	}, client, session)?.into_response())
+}
+
+/// When an event happens, in the UTC offset its `event.toml` was written in.
+fn event_time(event: &Row) -> String {
+	let offset = FixedOffset::east_opt(event.get("utc_offset")).unwrap_or(FixedOffset::east_opt(0).unwrap());
+	let start: Option<DateTime<Utc>> = event.get("start");
+	let stop: Option<DateTime<Utc>> = event.get("stop");
+	match (start.map(|start| start.with_timezone(&offset)), stop.map(|stop| stop.with_timezone(&offset))) {
+		(Some(start), Some(stop)) if start.date_naive() == stop.date_naive() =>
+			format!("{} - {}", start.format("%a, %b %-d %Y, %-I:%M %p"), stop.format("%-I:%M %p")),
+		(Some(start), Some(stop)) =>
+			format!("{} - {}", start.format("%a, %b %-d %Y, %-I:%M %p"), stop.format("%a, %b %-d %Y, %-I:%M %p")),
+		(Some(start), None) => start.format("%a, %b %-d %Y, %-I:%M %p").to_string(),
+		_ => String::new(),
+	}
+}
+
+/// Escapes text for an iCalendar property value.
+fn ics_text(text: &str) -> String {
+	text.replace('\\', "\\\\")
+		.replace(';', "\\;")
+		.replace(',', "\\,")
+		.replace('\n', "\\n")
+		.replace('\r', "")
+}
+
+/// Folds an iCalendar content line to 75 octets, as RFC 5545 requires.
+fn ics_line(calendar: &mut String, line: &str) {
+	let mut length = 0;
+	for c in line.chars() {
+		if length + c.len_utf8() > 75 {
+			calendar.push_str("\r\n ");
+			length = 1;
+		}
+		calendar.push(c);
+		length += c.len_utf8();
+	}
+	calendar.push_str("\r\n");
+}
+
+fn get_calendar(mut client: Client, base_url: String) -> Result<impl Reply, Rejection> {
+	let title: String = result!(client.query_one("SELECT title FROM scrap.ctf WHERE current", &[])).get("title");
+	let events = result!(client.query("SELECT id, title, start, stop, location, link, slides
+		FROM scrap.event
+		WHERE start IS NOT NULL
+		ORDER BY start ASC",
+		&[]));
+	// UIDs must stay the same however the feed is fetched, so they use the configured domain rather than the Host header.
+	let domain = base_url.split_once("://").map_or(base_url.as_str(), |(_, rest)| rest)
+		.split(['/', ':']).next().unwrap_or_default();
+	let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+	let mut calendar = String::new();
+	ics_line(&mut calendar, "BEGIN:VCALENDAR");
+	ics_line(&mut calendar, "VERSION:2.0");
+	ics_line(&mut calendar, "PRODID:-//ACM Cyber at UCLA//scrap//EN");
+	ics_line(&mut calendar, &format!("X-WR-CALNAME:{}", ics_text(&title)));
+	for event in &events {
+		let id: i32 = event.get("id");
+		let start: DateTime<Utc> = event.get("start");
+		let stop: Option<DateTime<Utc>> = event.get("stop");
+		let location: Option<String> = event.get("location");
+		let link: String = event.get("link");
+		let slides: String = event.get("slides");
+		ics_line(&mut calendar, "BEGIN:VEVENT");
+		ics_line(&mut calendar, &format!("UID:event-{}@{}", id, domain));
+		ics_line(&mut calendar, &format!("DTSTAMP:{}", stamp));
+		ics_line(&mut calendar, &format!("DTSTART:{}", start.format("%Y%m%dT%H%M%SZ")));
+		if let Some(stop) = stop {
+			ics_line(&mut calendar, &format!("DTEND:{}", stop.format("%Y%m%dT%H%M%SZ")));
+		}
+		ics_line(&mut calendar, &format!("SUMMARY:{}", ics_text(event.get("title"))));
+		if let Some(location) = location {
+			ics_line(&mut calendar, &format!("LOCATION:{}", ics_text(&location)));
+		}
+		if !link.is_empty() {
+			ics_line(&mut calendar, &format!("URL:{}", link));
+		}
+		if !slides.is_empty() {
+			ics_line(&mut calendar, &format!("DESCRIPTION:Slides: {}", ics_text(&slides)));
+		}
+		ics_line(&mut calendar, "END:VEVENT");
+	}
+	ics_line(&mut calendar, "END:VCALENDAR");
+	Ok(Response::builder()
+		.header("content-type", "text/calendar; charset=utf-8")
+		.body(calendar))
+}
+
+/// Challenges grouped by category, optionally filtered by `category`, `tag` and `status` (`solved` or `unsolved`).
+/// `submission` is the flash cookie left by `submit`, holding the outcome and percent-encoded slug of the last submission.
+fn get_challenges(mut client: Client, session: String, submission: String, query: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let submission = percent_decode_str(&submission).decode_utf8_lossy().into_owned();
+	let submission = submission.split_once(':')
+		.and_then(|(code, slug)| Outcome::parse(code).map(|outcome| (outcome, slug)));
 	let now = Utc::now();
-	let ctf = &result!(client.query("SELECT start, stop FROM scrap.ctf", &[]))[0];
+	let ctf = &result!(client.query("SELECT start, stop FROM scrap.ctf WHERE current", &[]))[0];
 	if ctf.try_get("start").map(|start: DateTime<Utc>| now < start).unwrap_or(false) {
 		return Ok(with_header(page("Challenges", html! {
 			h1 { "Challenges" }
+			@if let Some((outcome, slug)) = submission {
+				(outcome.flash(slug))
+			}
 			p { "Challenges are not available." }
-		}, client, session)?, "set-cookie", "invalid=; HttpOnly; SameSite=Lax; Max-Age=-1"));
+		}, client, session)?, "set-cookie", "submission=; HttpOnly; SameSite=Lax; Max-Age=-1"));
 	}
+	let practice = ctf.try_get("stop").map(|stop: DateTime<Utc>| now > stop).unwrap_or(false);
+	let filter = |name| query.get(name).filter(|value: &&String| !value.is_empty());
+	let (category, tag, status) = (filter("category"), filter("tag"), filter("status"));
 	let challenges = result!(client.query("SELECT
-		slug, title, author, description, tags, challenge.solves, challenge.value,
+		slug, title, author, description, tags, category, difficulty, challenge.solves, challenge.value,
 		team.id IS NOT NULL AS authenticated,
-		solved(team.solves, challenge.id) AS solved
+		solved(team.id, challenge.id) AS solved,
+		EXISTS (SELECT FROM scrap.practice practice
+			WHERE practice.team=team.id AND practice.challenge=challenge.id) AS practiced
 		FROM scrap.challenge challenge
 		LEFT JOIN scrap.team team ON team.id=lookup($1)
-		WHERE enabled=true
-		ORDER BY value ASC, slug ASC",
-		&[&session]));
+		WHERE enabled=true AND ctf=current_ctf()
+		AND ($2::TEXT IS NULL OR category=$2)
+		AND ($3::TEXT IS NULL OR $3=ANY(tags))
+		ORDER BY category ASC NULLS LAST, value ASC, slug ASC",
+		&[&session, &category, &tag]));
+	let mut groups: Vec<(Option<String>, Vec<&Row>)> = Vec::new();
+	for challenge in challenges.iter().filter(|challenge| {
+		let done = challenge.get::<_, bool>("solved") || challenge.get::<_, bool>("practiced");
+		match status.map(String::as_str) {
+			Some("solved") => done,
+			Some("unsolved") => !done,
+			_ => true,
+		}
+	}) {
+		let category: Option<String> = challenge.get("category");
+		match groups.last_mut() {
+			Some((last, group)) if *last == category => group.push(challenge),
+			_ => groups.push((category, vec![challenge])),
+		}
+	}
+	let categories: Vec<String> = result!(client.query("SELECT DISTINCT category FROM scrap.challenge
+		WHERE enabled=true AND ctf=current_ctf() AND category IS NOT NULL
+		ORDER BY category ASC", &[])).iter().map(|row| row.get("category")).collect();
+	let tags: Vec<String> = result!(client.query("SELECT DISTINCT UNNEST(tags) AS tag FROM scrap.challenge
+		WHERE enabled=true AND ctf=current_ctf()
+		ORDER BY tag ASC", &[])).iter().map(|row| row.get("tag")).collect();
+	let announcements = result!(client.query("SELECT challenge.slug, announcement.message, announcement.time
+		FROM scrap.announcement announcement
+		JOIN scrap.challenge challenge ON challenge.id=announcement.challenge
+		WHERE announcement.ctf=current_ctf()
+		ORDER BY announcement.time DESC",
+		&[]));
+	let banned = result!(client.query("SELECT banned FROM scrap.team
+		WHERE id=lookup($1)",
+		&[&session])).pop().map(|team| team.get("banned")).unwrap_or(false);
 	Ok(with_header(page("Challenges", html! {
 		h1 { "Challenges" }
-		section class="challenges tiles" {
-			ul {
-				@for challenge in &challenges {
-					@let slug: String = challenge.get("slug");
-					@let title: String = challenge.get("title");
-					@let author: String = challenge.get("author");
-					@let description: String = challenge.get("description");
-					@let tags: Vec<String> = challenge.get("tags");
-					@let solves: i32 = challenge.get("solves");
-					@let value: i32 = challenge.get("value");
-					@let authenticated: bool = challenge.get("authenticated");
-					@let solved: bool = challenge.get("solved");
-					li {
-						a solved?[solved] href={ "#" (slug) } {
-							div {
-								h1.value { (value) }
-								p.tags {
-									@for tag in &tags {
-										span { (tag) }
+		@if let Some((outcome, slug)) = submission {
+			(outcome.flash(slug))
+		}
+		@if banned {
+			p class="error" { "Your team has been disqualified and can no longer submit flags." }
+		} @else if practice {
+			p { "The CTF is over, but you can keep practicing. Flags submitted now are checked but not scored." }
+		}
+		form.filter method="GET" action="/challenges" {
+			select name="category" {
+				option value="" { "All categories" }
+				@for name in &categories {
+					option value=(name) selected?[category == Some(name)] { (name) }
+				}
+			}
+			select name="tag" {
+				option value="" { "All tags" }
+				@for name in &tags {
+					option value=(name) selected?[tag == Some(name)] { (name) }
+				}
+			}
+			select name="status" {
+				option value="" { "All challenges" }
+				option value="unsolved" selected?[status.map(String::as_str) == Some("unsolved")] { "Unsolved" }
+				option value="solved" selected?[status.map(String::as_str) == Some("solved")] { "Solved" }
+			}
+			button type="submit" { "Filter" }
+		}
+		@if groups.is_empty() {
+			p { "No challenges match." }
+		}
+		@for (name, group) in &groups {
+			@if groups.len() > 1 || name.is_some() {
+				h2 { (name.as_ref().map(String::as_str).unwrap_or("Uncategorized")) }
+			}
+			section class="challenges tiles" {
+				ul {
+					@for challenge in group {
+						@let slug: String = challenge.get("slug");
+						@let title: String = challenge.get("title");
+						@let author: String = challenge.get("author");
+						@let description: String = challenge.get("description");
+						@let tags: Vec<String> = challenge.get("tags");
+						@let solves: i32 = challenge.get("solves");
+						@let value: i32 = challenge.get("value");
+						@let authenticated: bool = challenge.get("authenticated");
+						@let solved: bool = challenge.get("solved");
+						@let practiced: bool = challenge.get("practiced");
+						@let difficulty: Option<String> = challenge.get("difficulty");
+						li {
+							a solved?[solved || practiced] href={ "#" (slug) } {
+								div {
+									h1.value { (value) }
+									@if let Some(difficulty) = &difficulty {
+										p.difficulty { (difficulty) }
+									}
+									p.tags {
+										@for tag in &tags {
+											span { (tag) }
+										}
 									}
 								}
 							}
-						}
-						div class="modal-container" id=(slug) {
-							dialog open="open" id=(slug) {
-								h1 { (title) }
-								p.value data=(value) { (value) }
-								p.solves data=(solves) { (solves) }
-								p.description { (PreEscaped(description)) }
-								p.author { (author) }
-								p.tags {
-									@for tag in &tags {
-										span { (tag) }
+							div class="modal-container" id=(slug) {
+								dialog open="open" id=(slug) {
+									h1 { a href={ "/challenges/" (slug) } { (title) } }
+									p.value data=(value) { (value) }
+									p.solves data=(solves) { (solves) }
+									@if let Some(difficulty) = &difficulty {
+										p.difficulty { (difficulty) }
 									}
-								}
-								@if authenticated && !solved {
-									form method="POST" {
-										input type="hidden" name="slug" value=(slug);
-										input type="text" name="flag" placeholder=(
-											if slug == invalid {
-												"incorrect flag"
-											} else {
-												"flag{...}"
-											});
-										button type="submit" { "Submit" }
+									div.announcements data-challenge=(slug) {
+										@for announcement in announcements.iter().filter(|announcement| announcement.get::<_, String>("slug") == slug) {
+											(make_announcement(announcement))
+										}
 									}
+									p.description { (PreEscaped(description)) }
+									p.author { (author) }
+									p.tags {
+										@for tag in &tags {
+											span { (tag) }
+										}
+									}
+									@if practiced && !solved {
+										p { "Solved in practice." }
+									}
+									@if authenticated && !solved && !practiced && !banned {
+										form method="POST" {
+											input type="hidden" name="slug" value=(slug);
+											input type="text" name="flag" placeholder=(
+												if submission == Some((Outcome::Incorrect, &slug)) {
+													"incorrect flag"
+												} else {
+													"flag{...}"
+												});
+											button type="submit" { "Submit" }
+										}
+									}
+									a class="close" href="#!" { "Close" }
 								}
-								a class="close" href="#!" { "Close" }
+								a class="modal-bg" href="#!" {}
 							}
-							a class="modal-bg" href="#!" {}
 						}
 					}
+					@for _ in 0..3 {
+						li {}
+					}
 				}
-				@for _ in 0..3 {
-					li {}
+			}
+		}
+	}, client, session)?, "set-cookie", "submission=; HttpOnly; SameSite=Lax; Max-Age=-1"))
+}
+
+/// A challenge's own page, with `result` holding the outcome of a flag just submitted from it.
+/// Returns `None` for challenges that are not visible.
+fn challenge_body(mut client: Client, session: String, slug: &str, result: Option<Outcome>) -> Result<Option<String>, Rejection> {
+	let now = Utc::now();
+	let ctf = &result!(client.query("SELECT start, stop FROM scrap.ctf WHERE current", &[]))[0];
+	if ctf.try_get("start").map(|start: DateTime<Utc>| now < start).unwrap_or(false) {
+		return Ok(None);
+	}
+	let practice = ctf.try_get("stop").map(|stop: DateTime<Utc>| now > stop).unwrap_or(false);
+	let challenge = match result!(client.query("SELECT
+		challenge.id, title, author, description, tags, category, difficulty, files, hints,
+		challenge.solves, challenge.value,
+		team.id IS NOT NULL AS authenticated,
+		COALESCE(team.banned, false) AS banned,
+		solved(team.id, challenge.id) AS solved,
+		EXISTS (SELECT FROM scrap.practice practice
+			WHERE practice.team=team.id AND practice.challenge=challenge.id) AS practiced
+		FROM scrap.challenge challenge
+		LEFT JOIN scrap.team team ON team.id=lookup($1)
+		WHERE slug=$2 AND enabled=true AND ctf=current_ctf()",
+		&[&session, &slug])).pop() {
+		Some(challenge) => challenge,
+		None => return Ok(None),
+	};
+	let id: i32 = challenge.get("id");
+	let solvers = result!(client.query("SELECT team.name, solve.time
+		FROM scrap.solve solve
+		JOIN scrap.team team ON team.id=solve.team
+		WHERE solve.challenge=$1 AND NOT team.hidden AND NOT team.banned
+		ORDER BY solve.time ASC",
+		&[&id]));
+	let announcements = result!(client.query("SELECT message, time FROM scrap.announcement
+		WHERE challenge=$1
+		ORDER BY time DESC",
+		&[&id]));
+	let title: String = challenge.get("title");
+	let author: String = challenge.get("author");
+	let description: String = challenge.get("description");
+	let tags: Vec<String> = challenge.get("tags");
+	let category: Option<String> = challenge.get("category");
+	let difficulty: Option<String> = challenge.get("difficulty");
+	let files: Vec<String> = challenge.get::<_, Option<Vec<String>>>("files").unwrap_or_default();
+	let hints: Vec<String> = challenge.get::<_, Option<Vec<String>>>("hints").unwrap_or_default();
+	let solves: i32 = challenge.get("solves");
+	let value: i32 = challenge.get("value");
+	let authenticated: bool = challenge.get("authenticated");
+	let banned: bool = challenge.get("banned");
+	let solved: bool = challenge.get("solved");
+	let practiced: bool = challenge.get("practiced");
+	Ok(Some(make_body(&title, html! {
+		h1 { (title) }
+		section {
+			p {
+				(value) " points"
+				@if let Some(category) = &category {
+					" · " a href={ "/challenges?category=" (category) } { (category) }
+				}
+				@if let Some(difficulty) = &difficulty {
+					" · " (difficulty)
+				}
+			}
+			div.announcements data-challenge=(slug) {
+				@for announcement in &announcements {
+					(make_announcement(announcement))
+				}
+			}
+			div.description { (PreEscaped(description)) }
+			p.author { (author) }
+			p.tags {
+				@for tag in &tags {
+					a href={ "/challenges?tag=" (tag) } { (tag) } " "
+				}
+			}
+		}
+		@if !files.is_empty() {
+			section {
+				h2 { "Files" }
+				ul {
+					@for file in &files {
+						li { a href=(file) { (file.rsplit('/').next().unwrap_or(file)) } }
+					}
+				}
+			}
+		}
+		@if !hints.is_empty() {
+			section {
+				h2 { "Hints" }
+				@for (hint, number) in hints.iter().zip(1..) {
+					details {
+						summary { "Hint " (number) }
+						div { (PreEscaped(hint)) }
+					}
+				}
+			}
+		}
+		section {
+			h2 { "Flag" }
+			@if let Some(outcome) = result {
+				@if outcome.correct() {
+					p { (outcome.message()) }
+				} @else {
+					p class="error" { (outcome.message()) }
+				}
+			}
+			@if solved {
+				p { "Your team has solved this challenge." }
+			} @else if practiced {
+				p { "Solved in practice." }
+			} @else if banned {
+				p class="error" { "Your team has been disqualified and can no longer submit flags." }
+			} @else if authenticated {
+				@if practice {
+					p { "The CTF is over, but you can keep practicing. Flags submitted now are checked but not scored." }
+				}
+				form method="POST" action={ "/challenges/" (slug) } {
+					input type="text" name="flag" placeholder="flag{...}";
+					button type="submit" { "Submit" }
+				}
+			} @else {
+				p { a href="/profile" { "Join a team" } " to submit flags." }
+			}
+		}
+		section {
+			h2 { (solves) " Solves" }
+			@if !solvers.is_empty() {
+				table {
+					thead {
+						tr {
+							th { "#" }
+							th { "Team" }
+							th { "Time" }
+						}
+					}
+					tbody {
+						@for (solver, place) in solvers.iter().zip(1..) {
+							@let name: String = solver.get("name");
+							@let time: DateTime<Utc> = solver.get("time");
+							tr {
+								td { (place) }
+								td { (name) }
+								td { (time.format("%Y-%m-%d %H:%M:%S UTC")) }
+							}
+						}
+					}
 				}
 			}
 		}
-	}, client, session)?, "set-cookie", "invalid=; HttpOnly; SameSite=Lax; Max-Age=-1"))
+		p { a href="/challenges" { "All challenges" } }
+	}, client, session)?))
+}
+
+fn get_challenge(client: Client, session: String, slug: String) -> Result<impl Reply, Rejection> {
+	match challenge_body(client, session, &slug, None)? {
+		Some(body) => Ok(make_reply(body).into_response()),
+		None => Ok(Response::builder()
+			.status(StatusCode::NOT_FOUND)
+			.body("404 Page Not Found".to_string())
+			.into_response()),
+	}
+}
+
+/// Submits a flag from a challenge's page and shows the result on it.
+fn submit_challenge(mut client: Client, session: String, slug: String, hub: Arc<Hub>, webhooks: Arc<Webhooks>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let empty = String::new();
+	let flag = form.get("flag").unwrap_or(&empty);
+	let outcome = submit_flag(&mut client, &session, &hub, &webhooks, &slug, flag)?;
+	match challenge_body(client, session, &slug, Some(outcome))? {
+		Some(body) => Ok(make_reply(body).into_response()),
+		None => Ok(Response::builder()
+			.status(StatusCode::NOT_FOUND)
+			.body("404 Page Not Found".to_string())
+			.into_response()),
+	}
+}
+
+/// Teams ranked overall, or within a division among teams whose members are all approved for it.
+fn get_standings(client: &mut Client, division: Option<&String>) -> Result<Vec<Row>, Rejection> {
+	Ok(result!(client.query("SELECT id, name, score,
+		ARRAY(SELECT challenge FROM scrap.solve WHERE team=team.id AND ctf=current_ctf()) AS solves,
+		ROW_NUMBER() OVER (ORDER BY score DESC, submit ASC) AS place FROM scrap.team team
+		WHERE NOT hidden AND NOT banned
+		AND ($1::TEXT IS NULL OR team_division(id)=$1)
+		ORDER BY score DESC, submit ASC",
+		&[&division])))
+}
+
+fn get_announcements(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+	let announcements = result!(client.query("SELECT announcement.message, announcement.time, challenge.title
+		FROM scrap.announcement announcement
+		LEFT JOIN scrap.challenge challenge ON challenge.id=announcement.challenge
+		WHERE announcement.ctf=current_ctf()
+		ORDER BY announcement.time DESC",
+		&[]));
+	page("Announcements", html! {
+		h1 { "Announcements" }
+		section class="announcements" {
+			@for announcement in &announcements {
+				@let title: Option<String> = announcement.get("title");
+				@if let Some(title) = title { h2 { (title) } }
+				(make_announcement(announcement))
+			}
+		}
+	}, client, session)
 }
 
-fn get_scoreboard(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+fn get_scoreboard(mut client: Client, session: String, query: HashMap<String, String>) -> Result<impl Reply, Rejection> {
 	let now = Utc::now();
-	let ctf = &result!(client.query("SELECT start, stop FROM scrap.ctf", &[]))[0];
+	let ctf = &result!(client.query("SELECT start, stop FROM scrap.ctf WHERE current", &[]))[0];
 	if ctf.try_get("start").map(|start: DateTime<Utc>| now < start).unwrap_or(false) {
 		return Ok(page("Scoreboard", html! {
 			h1 { "Scoreboard" }
 			p { "Scoreboard is not available." }
 		}, client, session)?);
 	}
-	let teams = result!(client.query("SELECT name, score, solves, ROW_NUMBER()
-		OVER (ORDER BY score DESC, submit ASC) AS place FROM scrap.team ORDER BY score DESC, submit ASC", &[]));
+	let division = query.get("division");
+	let divisions = get_divisions(&mut client)?;
+	let teams = get_standings(&mut client, division)?;
 	let challenges = result!(client.query("SELECT id, title FROM scrap.challenge
-		WHERE enabled=true
+		WHERE enabled=true AND ctf=current_ctf()
 		ORDER BY slug ASC", &[]));
 	Ok(page("Scoreboard", html! {
 		h1 { "Scoreboard" }
+		@if !divisions.is_empty() {
+			p class="divisions" {
+				a.current[division.is_none()] href="/scoreboard" { "Overall" }
+				@for row in &divisions {
+					@let slug: String = row.get("slug");
+					@let name: String = row.get("name");
+					" "
+					a.current[division == Some(&slug)] href={ "/scoreboard?division=" (slug) } { (name) }
+				}
+			}
+		}
 		section class="scoreboard" {
 			table {
 				thead {
@@ -283,415 +852,3357 @@ fn get_scoreboard(mut client: Client, session: String) -> Result<impl Reply, Rej
 				}
 				tbody {
 					@for team in teams {
+						@let id: i32 = team.get("id");
 						@let name: String = team.get("name");
-						@let solves: i64 = team.get("solves");
+						@let solves: Vec<i32> = team.get("solves");
 						@let score: i32 = team.get("score");
 						@let place: i64 = team.get("place");
 						tr {
 							td class="place" { (place) }
-							td class="team" { (name) }
+							td class="team" { a href={ "/teams/" (id) } { (name) } }
 							td class="score" { (score) }
 							@for challenge in &challenges {
 								@let id: i32 = challenge.get("id");
-								@let mask: i64 = 1 << (id - 1);
-								td class="challenge" solved?[mask & solves > 0];
+								td class="challenge" solved?[solves.contains(&id)];
 							}
 						}
 					}
 				}
 			}
+			p {
+				a href={ "/scoreboard.json" @if let Some(division) = division { "?division=" (division) } } { "JSON" }
+				" "
+				a href="/archive" { "Past CTFs" }
+			}
 		}
 	}, client, session)?)
 }
 
-// fn get_internship(client: Client, session: String) -> Result<impl Reply, Rejection> {
-// 	Ok(page("Internship", html! {
-// 		h1 { "All About the Cyber Internship" }
-// 		h2 { "Being a Cyber Intern"}
-// 		p { "You might be wondering....what's it take to be a cyber intern? Do I have to be the greatest hacker of all time?" }
-// 		p { "The answer is a resounding no. We want people who are excited to learn and teach about cyber to join us!"}
-// 		p {" As a cyber intern you'll work alongside officers to conceptualize and build workshops. You will get to concoct your own challenges for both workshops and the CTF After Dark events. But don't worry! You will have a set of mentors (and the whole team) to help you along the way. "}
-// 		p {" After a quarter of serving as a Cyber intern, you will *ascend* to officer."}
-// 		h2 { "Applying"}
-// 		p {"If you are on the ACM general mailing list, you'll receive an email when all intern program applications (not just Cyber) are open. Use that link and answer the Cyber specific questions."}
-// 		p {"From there you'll receive updates about next steps, likely an interview with a current officer. It is not a rigorous process and overall we want you to be 100% yourself. You don't need to be the world's up-and-coming white hat hacker to fit with the Cyber team :-)"}
-// 		h2 { "Do we get paid?"}
-// 		p {"No"}
-// 	}, client, session)?)
-// }
-
-fn get_pbr(client: Client, session: String) -> Result<impl Reply, Rejection> {
-	Ok(page("Psi Beta Rho", html! {
-		h1 { "Psi Beta Rho" }
-		h2 { "What is Psi Beta Rho?"}
-		p { "Psi Beta Rho, also known as PBR, is UCLA's competetive cybersecurity team! We are a group of students who are passionate about learning more about cybersecurity and competing at CTFs. PBR attends a variety of different cybersecurity competitions throughout the year by we primarily compete in capture the flag (CTF) competitions." }
-		h2 { "What are CTFs?"}
-		p {"CTFs or capture the flag competitions are jeopardy style cybersecurity competitions where teams compete to solve a variety of problems from various different categories. The goal of each challenge is to find a particular flag which is some sort of string which can be submitted to earn points. For an example of what CTF challenges look like, be sure to check out the Challenges page on this site."}
-		h2 { "Do I need to have a ton of cybersecurity experience?"}
-		p { "No! Psi Beta Rho is open to all skill levels. Whether you are just launching your cybersecurity career or are already an experienced professional, PBR is for you!" }
-		h2 { "How can I join?" }
-		p { "If you are on the ACM Cyber mailing list, we will make an announcement when applications to join PBR are open. We also run weekly practices that are open to any UCLA student so feel free to stop by to check out the team! For more information or to ask questions, be sure to join the ACM Cyber Discord." }
-	}, client, session)?)
+fn get_archive(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+	let seasons = result!(client.query("SELECT ctf.slug, ctf.title, ctf.start, ctf.stop, standing.name AS winner
+		FROM scrap.ctf ctf
+		LEFT JOIN scrap.standing standing ON standing.ctf=ctf.slug AND standing.place=1
+		WHERE ctf.archived IS NOT NULL
+		ORDER BY COALESCE(ctf.stop, ctf.archived) DESC",
+		&[]));
+	page("Archive", html! {
+		h1 { "Past CTFs" }
+		@if seasons.is_empty() {
+			p { "No CTFs have been archived yet." }
+		}
+		section {
+			table {
+				tbody {
+					@for season in &seasons {
+						@let slug: String = season.get("slug");
+						@let title: String = season.get("title");
+						@let stop: Option<DateTime<Utc>> = season.get("stop");
+						@let winner: Option<String> = season.get("winner");
+						tr {
+							td { a href={ "/archive/" (slug) } { (title) } }
+							td { @if let Some(stop) = stop { (stop.format("%Y-%m-%d")) } }
+							td { @if let Some(winner) = winner { "Won by " (winner) } }
+						}
+					}
+				}
+			}
+		}
+	}, client, session)
 }
 
-fn make_profile(team: Option<Row>, error: Option<&str>) -> Markup {
-	html! {
-		h1 { "Profile" }
-		section class="profile" {
-			@if let Some(error) = error { p class="error" { (error) } }
-			@match team {
-				Some(team) => {
-					@let name: String = team.get("name");
-					@let discord: String = team.get("discord");
-					form method="POST" {
-						label {
-							"Team Name: "
-							input type="text" disabled="disabled" value=(name);
-						}
-						label {
-							"Discord: "
-							input type="text" name="discord" value=(discord);
-						}
-						label {
-							"Password: "
-							input type="password" name="password" placeholder="Optional";
+/// A finished CTF's final standings, with its challenges open for practice. `result` is the
+/// challenge a flag was just checked for and the outcome of the check.
+fn season_body(mut client: Client, session: String, slug: &str, result: Option<(&str, Outcome)>) -> Result<Option<String>, Rejection> {
+	let season = match result!(client.query("SELECT title, start, stop FROM scrap.ctf
+		WHERE slug=$1 AND archived IS NOT NULL",
+		&[&slug])).pop() {
+		Some(season) => season,
+		None => return Ok(None),
+	};
+	let standings = result!(client.query("SELECT place, name, score, solves, division FROM scrap.standing
+		WHERE ctf=$1
+		ORDER BY place ASC",
+		&[&slug]));
+	let challenges = result!(client.query("SELECT slug, title, author, value, description, tags, solves FROM scrap.challenge
+		WHERE ctf=$1 AND enabled=true
+		ORDER BY value ASC, slug ASC",
+		&[&slug]));
+	let title: String = season.get("title");
+	let start: Option<DateTime<Utc>> = season.get("start");
+	let stop: Option<DateTime<Utc>> = season.get("stop");
+	Ok(Some(make_body(&title, html! {
+		h1 { (title) }
+		@if let (Some(start), Some(stop)) = (start, stop) {
+			p { (start.format("%Y-%m-%d %H:%M UTC")) " - " (stop.format("%Y-%m-%d %H:%M UTC")) }
+		}
+		section class="scoreboard" {
+			h2 { "Final Standings" }
+			table {
+				thead {
+					tr {
+						th class="place" { "#" }
+						th class="team" { "Team" }
+						th class="score" { "Score" }
+						th { "Solves" }
+					}
+				}
+				tbody {
+					@for standing in &standings {
+						@let place: i64 = standing.get("place");
+						@let name: String = standing.get("name");
+						@let score: i32 = standing.get("score");
+						@let solves: i64 = standing.get("solves");
+						tr {
+							td class="place" { (place) }
+							td class="team" { (name) }
+							td class="score" { (score) }
+							td { (solves) }
 						}
-						label {
-							"Current Password: "
-							input type="password" name="current_password";
+					}
+				}
+			}
+		}
+		section {
+			h2 { "Practice" }
+			p { "Flags checked here are not scored." }
+			@if let Some((checked, Outcome::NoSuchChallenge)) = result {
+				(Outcome::NoSuchChallenge.flash(checked))
+			}
+			@for challenge in &challenges {
+				@let challenge_slug: String = challenge.get("slug");
+				@let challenge_title: String = challenge.get("title");
+				@let author: String = challenge.get("author");
+				@let value: i32 = challenge.get("value");
+				@let description: String = challenge.get("description");
+				@let solves: i32 = challenge.get("solves");
+				@let checked = result.filter(|(checked, _)| *checked == challenge_slug).map(|(_, outcome)| outcome);
+				details id=(challenge_slug) open?[checked.is_some()] {
+					summary { (challenge_title) " (" (value) " points, " (solves) " solves)" }
+					div { (PreEscaped(description)) }
+					p.author { (author) }
+					@if let Some(outcome) = checked {
+						@if outcome.correct() {
+							p { (outcome.message()) }
+						} @else {
+							p class="error" { (outcome.message()) }
 						}
-						button type="submit" { "Save" }
 					}
-				},
-				None => {
-					p class="not-logged-in" { "Log in to view your profile." }
+					form method="POST" action={ "/archive/" (slug) "#" (challenge_slug) } {
+						input type="hidden" name="slug" value=(challenge_slug);
+						input type="text" name="flag" placeholder="flag{...}";
+						button type="submit" { "Check" }
+					}
 				}
 			}
 		}
+	}, client, session)?))
+}
+
+fn get_season(client: Client, session: String, slug: String) -> Result<impl Reply, Rejection> {
+	match season_body(client, session, &slug, None)? {
+		Some(body) => Ok(make_reply(body).into_response()),
+		None => Ok(Response::builder()
+			.status(StatusCode::NOT_FOUND)
+			.body("404 Page Not Found".to_string())
+			.into_response()),
 	}
 }
 
-fn get_profile(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
-	let team = match client.query("SELECT name, discord FROM scrap.team
-		WHERE id=lookup($1)",
-		&[&session]) {
-		Ok(mut teams) => teams.pop(),
-		Err(e) => return Err(custom(e)),
+fn check_archive(mut client: Client, session: String, slug: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let empty = String::new();
+	let challenge = form.get("slug").unwrap_or(&empty);
+	let flag = form.get("flag").unwrap_or(&empty);
+	let outcome = match result!(client.query("SELECT challenge.flag FROM scrap.challenge challenge
+		JOIN scrap.ctf ctf ON ctf.slug=challenge.ctf
+		WHERE challenge.ctf=$1 AND challenge.slug=$2 AND challenge.enabled=true
+		AND ctf.archived IS NOT NULL",
+		&[&slug, &challenge])).pop() {
+		Some(row) if row.get::<_, String>("flag") == *flag => Outcome::Correct,
+		Some(_) => Outcome::Incorrect,
+		None => Outcome::NoSuchChallenge,
 	};
-	Ok(page("Profile", make_profile(team, None), client, session)?)
+	match season_body(client, session, &slug, Some((challenge, outcome)))? {
+		Some(body) => Ok(make_reply(body).into_response()),
+		None => Ok(Response::builder()
+			.status(StatusCode::NOT_FOUND)
+			.body("404 Page Not Found".to_string())
+			.into_response()),
+	}
 }
 
-fn make_register(error: Option<&str>) -> Markup {
-	html! {
-		h1 { "Register" }
-		section class="register" {
-			@if let Some(error) = error { p class="error" { (error) } }
-			form method="POST" {
-				input type="text" name="name" placeholder="Team Name" maxlength="64" pattern="[ -~]+";
-				input type="text" name="discord" placeholder="Discord handle (eg. cyber#1234)";
-				input type="password" name="password" placeholder="Password";
-				button type="submit" { "Register" }
+/// A team's rank, solves and members. Admins also see hidden and banned teams, wrong flags and sessions.
+fn get_team(mut client: Client, session: String, id: i32) -> Result<impl Reply, Rejection> {
+	let admin = is_admin(&mut client, &session)?;
+	let team = match result!(client.query("SELECT team.name, team.score, team.hidden, team.banned,
+		division.slug AS division, division.name AS division_name
+		FROM scrap.team team
+		LEFT JOIN scrap.division division ON division.slug=team_division(team.id)
+		WHERE team.id=$1 AND ($2 OR NOT (team.hidden OR team.banned))",
+		&[&id, &admin])).pop() {
+		Some(team) => team,
+		None => return Ok(Response::builder()
+			.status(StatusCode::NOT_FOUND)
+			.header("content-security-policy", "script-src 'none'")
+			.body(make_body("Teams", html! {
+				h1 { "Teams" }
+				p { "No such team." }
+			}, client, session)?)
+			.into_response()),
+	};
+	let place = get_standings(&mut client, None)?.iter()
+		.find(|standing| standing.get::<_, i32>("id") == id)
+		.map(|standing| standing.get::<_, i64>("place"));
+	let solves = result!(client.query("SELECT challenge.slug, challenge.title, challenge.value, member.name, solve.time
+		FROM scrap.solve solve
+		JOIN scrap.challenge challenge ON challenge.id=solve.challenge
+		LEFT JOIN scrap.member member ON member.id=solve.member
+		WHERE solve.team=$1 AND solve.ctf=current_ctf()
+		ORDER BY solve.time ASC",
+		&[&id]));
+	let members = result!(client.query("SELECT name, captain FROM scrap.member
+		WHERE team=$1
+		ORDER BY joined ASC NULLS FIRST, id ASC",
+		&[&id]));
+	let (submissions, sessions) = if admin {
+		(result!(client.query("SELECT challenge.slug, member.name, submission.flag, submission.time
+			FROM scrap.submission submission
+			JOIN scrap.challenge challenge ON challenge.id=submission.challenge
+			LEFT JOIN scrap.member member ON member.id=submission.member
+			WHERE submission.team=$1 AND challenge.ctf=current_ctf()
+			ORDER BY submission.time DESC",
+			&[&id])),
+		result!(client.query("SELECT member.name, session.agent, session.created, session.seen
+			FROM scrap.session session
+			JOIN scrap.member member ON member.id=session.member
+			WHERE member.team=$1 AND NOT expired(session.created, session.seen)
+			ORDER BY session.seen DESC",
+			&[&id])))
+	} else {
+		(Vec::new(), Vec::new())
+	};
+	let name: String = team.get("name");
+	let score: i32 = team.get("score");
+	let hidden: bool = team.get("hidden");
+	let banned: bool = team.get("banned");
+	let division: Option<String> = team.get("division");
+	let division_name: Option<String> = team.get("division_name");
+	Ok(page(&name, html! {
+		h1 { (name) }
+		@if hidden {
+			p class="error" { "This team is hidden from the scoreboard." }
+		}
+		@if banned {
+			p class="error" { "This team has been disqualified." }
+		}
+		section {
+			p {
+				@if let Some(place) = place {
+					"Rank " (place) " · "
+				}
+				(score) " points"
+			}
+			@if let (Some(division), Some(division_name)) = (division, division_name) {
+				p { "Division: " a href={ "/scoreboard?division=" (division) } { (division_name) } }
+			}
+		}
+		section {
+			h2 { "Members" }
+			ul {
+				@for member in &members {
+					@let member_name: String = member.get("name");
+					@let captain: bool = member.get("captain");
+					li {
+						(member_name)
+						@if captain {
+							" (captain)"
+						}
+					}
+				}
+			}
+		}
+		section {
+			h2 { "Solves" }
+			@if solves.is_empty() {
+				p { "No solves yet." }
+			} @else {
+				table {
+					thead {
+						tr {
+							th { "Challenge" }
+							th { "Points" }
+							th { "Solved By" }
+							th { "Time" }
+						}
+					}
+					tbody {
+						@for solve in &solves {
+							@let slug: String = solve.get("slug");
+							@let title: String = solve.get("title");
+							@let value: i32 = solve.get("value");
+							@let solver: Option<String> = solve.get("name");
+							@let time: DateTime<Utc> = solve.get("time");
+							tr {
+								td { a href={ "/challenges/" (slug) } { (title) } }
+								td { (value) }
+								td { (solver.unwrap_or_default()) }
+								td { (time.format("%Y-%m-%d %H:%M:%S UTC")) }
+							}
+						}
+					}
+				}
+			}
+		}
+		@if admin {
+			section {
+				h2 { "Wrong Submissions" }
+				table {
+					thead {
+						tr {
+							th { "Challenge" }
+							th { "Member" }
+							th { "Flag" }
+							th { "Time" }
+						}
+					}
+					tbody {
+						@for submission in &submissions {
+							@let slug: String = submission.get("slug");
+							@let member: Option<String> = submission.get("name");
+							@let flag: String = submission.get("flag");
+							@let time: DateTime<Utc> = submission.get("time");
+							tr {
+								td { (slug) }
+								td { (member.unwrap_or_default()) }
+								td { code { (flag) } }
+								td { (time.format("%Y-%m-%d %H:%M:%S UTC")) }
+							}
+						}
+					}
+				}
+			}
+			section {
+				h2 { "Sessions" }
+				table class="sessions" {
+					thead {
+						tr {
+							th { "Member" }
+							th { "Device" }
+							th { "Signed In" }
+							th { "Last Seen" }
+						}
+					}
+					tbody {
+						@for row in &sessions {
+							@let member: String = row.get("name");
+							@let agent: Option<String> = row.get("agent");
+							@let created: DateTime<Utc> = row.get("created");
+							@let seen: DateTime<Utc> = row.get("seen");
+							tr {
+								td { (member) }
+								td { (agent.unwrap_or_else(|| "Unknown".to_string())) }
+								td { (created.format("%Y-%m-%d %H:%M UTC")) }
+								td { (seen.format("%Y-%m-%d %H:%M UTC")) }
+							}
+						}
+					}
+				}
+			}
+		}
+	}, client, session)?.into_response())
+}
+
+/// Big screen view for in-person events, kept current by `/static/projector.js`.
+fn get_scoreboard_live(mut client: Client) -> Result<impl Reply, Rejection> {
+	let now = Utc::now();
+	let ctf = &result!(client.query("SELECT title, start FROM scrap.ctf WHERE current", &[]))[0];
+	let title: String = ctf.get("title");
+	let available = !ctf.try_get("start").map(|start: DateTime<Utc>| now < start).unwrap_or(false);
+	let teams = if available { get_standings(&mut client, None)? } else { Vec::new() };
+	let solves = result!(client.query("SELECT team.name, challenge.title, solve.time
+		FROM scrap.solve solve
+		JOIN scrap.team team ON team.id=solve.team
+		JOIN scrap.challenge challenge ON challenge.id=solve.challenge
+		WHERE solve.ctf=current_ctf() AND NOT team.hidden AND NOT team.banned
+		ORDER BY solve.time DESC
+		LIMIT 10",
+		&[]));
+	let announcements = result!(client.query("SELECT message, time FROM scrap.announcement
+		WHERE challenge IS NULL AND ctf=current_ctf() AND time > NOW() - INTERVAL '1 day'
+		ORDER BY time DESC
+		LIMIT 3",
+		&[]));
+	Ok(make_reply(html! {
+		(DOCTYPE)
+		html {
+			head {
+				meta charset="utf-8";
+				meta name="viewport" content="width=device-width, initial-scale=1";
+				title { "Scoreboard | " (title) }
+				link rel="stylesheet" href="/static/style.css";
+				link rel="icon" type="image/png" href="/static/logo.svg";
+			}
+			body class="projector" {
+				h1 { (title) }
+				aside id="announcements" class="announcements" {
+					@for announcement in &announcements {
+						(make_announcement(announcement))
+					}
+				}
+				main {
+					section class="standings" {
+						table {
+							tbody {
+								@for team in teams.iter().take(PROJECTOR_TEAMS) {
+									@let place: i64 = team.get("place");
+									@let name: String = team.get("name");
+									@let score: i32 = team.get("score");
+									tr {
+										td { (place) }
+										td { (name) }
+										td { (score) }
+									}
+								}
+							}
+						}
+					}
+					section class="solves" {
+						h2 { "Recent Solves" }
+						ul {
+							@for solve in &solves {
+								@let team: String = solve.get("name");
+								@let challenge: String = solve.get("title");
+								@let time: DateTime<Utc> = solve.get("time");
+								li { strong { (time.format("%H:%M:%S")) } " " (team) " solved " (challenge) }
+							}
+						}
+					}
+				}
+				script src="/static/projector.js" {}
 			}
 		}
+	}.into_string()))
+}
+
+#[derive(Serialize)]
+struct Standing {
+	pos: i64,
+	team: String,
+	score: i32,
+}
+
+#[derive(Serialize)]
+struct Standings {
+	standings: Vec<Standing>,
+}
+
+fn make_standings(teams: &[Row]) -> Standings {
+	Standings {
+		standings: teams.iter().map(|team| Standing {
+			pos: team.get("place"),
+			team: team.get("name"),
+			score: team.get("score"),
+		}).collect(),
 	}
 }
 
-fn get_register(client: Client, session: String) -> Result<impl Reply, Rejection> {
-	Ok(page("Register", make_register(None), client, session)?)
+/// Tells live scoreboards about new standings. Called after the change is committed, so failures are only logged.
+fn publish_scoreboard(client: &mut Client, hub: &Hub) {
+	match get_standings(client, None) {
+		Ok(teams) => hub.publish("scoreboard", &make_standings(&teams)),
+		Err(e) => eprintln!("Failed to publish scoreboard: {:?}", e),
+	}
 }
 
-fn make_login(error: Option<&str>) -> Markup {
+/// Scoreboard in the CTFtime feed format, optionally for a single division.
+fn get_scoreboard_json(mut client: Client, query: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let now = Utc::now();
+	let ctf = &result!(client.query("SELECT start FROM scrap.ctf WHERE current", &[]))[0];
+	let teams = if ctf.try_get("start").map(|start: DateTime<Utc>| now < start).unwrap_or(false) {
+		Vec::new()
+	}
+	else {
+		get_standings(&mut client, query.get("division"))?
+	};
+	Ok(reply::json(&make_standings(&teams)))
+}
+
+// fn get_internship(client: Client, session: String) -> Result<impl Reply, Rejection> {
+// 	Ok(page("Internship", html! {
+// 		h1 { "All About the Cyber Internship" }
+// 		h2 { "Being a Cyber Intern"}
+// 		p { "You might be wondering....what's it take to be a cyber intern? Do I have to be the greatest hacker of all time?" }
+// 		p { "The answer is a resounding no. We want people who are excited to learn and teach about cyber to join us!"}
+// 		p {" As a cyber intern you'll work alongside officers to conceptualize and build workshops. You will get to concoct your own challenges for both workshops and the CTF After Dark events. But don't worry! You will have a set of mentors (and the whole team) to help you along the way. "}
+// 		p {" After a quarter of serving as a Cyber intern, you will *ascend* to officer."}
+// 		h2 { "Applying"}
+// 		p {"If you are on the ACM general mailing list, you'll receive an email when all intern program applications (not just Cyber) are open. Use that link and answer the Cyber specific questions."}
+// 		p {"From there you'll receive updates about next steps, likely an interview with a current officer. It is not a rigorous process and overall we want you to be 100% yourself. You don't need to be the world's up-and-coming white hat hacker to fit with the Cyber team :-)"}
+// 		h2 { "Do we get paid?"}
+// 		p {"No"}
+// 	}, client, session)?)
+// }
+
+fn get_pbr(client: Client, session: String) -> Result<impl Reply, Rejection> {
+	Ok(page("Psi Beta Rho", html! {
+		h1 { "Psi Beta Rho" }
+		h2 { "What is Psi Beta Rho?"}
+		p { "Psi Beta Rho, also known as PBR, is UCLA's competetive cybersecurity team! We are a group of students who are passionate about learning more about cybersecurity and competing at CTFs. PBR attends a variety of different cybersecurity competitions throughout the year by we primarily compete in capture the flag (CTF) competitions." }
+		h2 { "What are CTFs?"}
+		p {"CTFs or capture the flag competitions are jeopardy style cybersecurity competitions where teams compete to solve a variety of problems from various different categories. The goal of each challenge is to find a particular flag which is some sort of string which can be submitted to earn points. For an example of what CTF challenges look like, be sure to check out the Challenges page on this site."}
+		h2 { "Do I need to have a ton of cybersecurity experience?"}
+		p { "No! Psi Beta Rho is open to all skill levels. Whether you are just launching your cybersecurity career or are already an experienced professional, PBR is for you!" }
+		h2 { "How can I join?" }
+		p { "If you are on the ACM Cyber mailing list, we will make an announcement when applications to join PBR are open. We also run weekly practices that are open to any UCLA student so feel free to stop by to check out the team! For more information or to ask questions, be sure to join the ACM Cyber Discord." }
+	}, client, session)?)
+}
+
+fn get_rewards(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+	let team = result!(client.query("SELECT score - redeemed_score AS points, premium_tickets, ticket_points
+		FROM scrap.team, scrap.ctf
+		WHERE team.id=lookup($1) AND ctf.current",
+		&[&session])).pop();
+	let team = match team {
+		Some(team) => team,
+		None => {
+			let member: Option<i32> = result!(client.query_one("SELECT lookup_member($1)", &[&session])).get(0);
+			return page("Rewards", html! {
+				h1 { "Rewards" }
+				p {
+					@if member.is_some() {
+						"Join a team from your " a href="/profile" { "profile" } " to earn rewards."
+					} @else {
+						"Login to view rewards."
+					}
+				}
+			}, client, session);
+		},
+	};
+	let points: i32 = team.get("points");
+	let ticket_points: i32 = team.get("ticket_points");
+	let regular_tickets = points.max(0) / ticket_points;
+	let catalog = result!(client.query("SELECT name FROM scrap.catalog
+		WHERE weight > 0
+		ORDER BY position ASC",
+		&[]));
+	let grants = result!(client.query("SELECT max_value, tickets FROM scrap.grant
+		ORDER BY position ASC",
+		&[]));
+	let inventory = result!(client.query("SELECT name, quantity FROM scrap.inventory
+		WHERE quantity > 0
+		ORDER BY id ASC",
+		&[]));
+	let premium_tickets: i32 = team.get("premium_tickets");
+	let history = result!(client.query("SELECT prize, member.name AS member, time FROM (
+			SELECT prize, member, time FROM scrap.draw WHERE team=lookup($1)
+			UNION ALL
+			SELECT COALESCE('Raffle winner: ' || inventory.name, 'Raffle entry'), raffle.member, raffle.time
+			FROM scrap.raffle raffle
+			LEFT JOIN scrap.winner winner ON winner.entry=raffle.id
+			LEFT JOIN scrap.inventory inventory ON inventory.id=winner.inventory
+			WHERE raffle.team=lookup($1)
+		) history
+		LEFT JOIN scrap.member member ON member.id=history.member
+		ORDER BY time DESC",
+		&[&session]));
+	page("Rewards", html! {
+		script src="https://ajax.googleapis.com/ajax/libs/jquery/3.5.1/jquery.min.js" {}
+		h1 { "Rewards" }
+		section class="prizes tiles" {
+			ul {
+				li {
+					form method="POST" action="/rewards/redeem" {
+						input type="hidden" name="type" value="regular";
+						button type="submit" class="gacha-tiles" id="regular-tile" style="background-color:#ffe5a1;" { span { "Regular" } }
+					}
+				}
+				li {
+					form method="POST" action="/rewards/redeem" {
+						input type="hidden" name="type" value="premium";
+						button type="submit" class="gacha-tiles" id="premium-tile" { span { "Premium" } }
+					}
+				}
+			}
+		}
+		section {
+			div class="tickets" {
+				h2 { "You have" }
+				h3 { (regular_tickets) " Regular Ticket(s)" }
+				h3 { (premium_tickets) " Premium Ticket(s)" }
+			}
+			a href="#help" { button class="help" { "How does this work?" } }
+			div class="challenges" {
+				div class="challenges modal-container" id="help" {
+					dialog open="open" id="help" {
+						h1 { a href="https://tinyurl.com/y5l4brzq" target="_blank" rel="noreferrer noopener" { "What is going on here?" } }
+						p { "This quarter, you can earn tickets to redeem special prizes!" }
+						dl {
+							dt { h2 { "Regular Prizes" } }
+							dd {
+								"You can earn a regular ticket from solving " a href="/challenges" { "challenges" } "! "
+								(ticket_points) " points gets you 1 regular ticket, which can be exchanged for a random regular prize in the gacha."
+							}
+							dd {
+								"Regular prizes include:"
+								ul class="prizes regular" {
+									@for prize in &catalog {
+										li { (prize.get::<_, String>("name")) }
+									}
+								}
+							}
+							dt { h2 { "Premium Prizes" } }
+							dd {
+								"You can earn premium tickets by submitting a writeup " a href="http://links.uclaacm.com/cyberwriteup" target="_blank" rel="noreferrer noopener" { "here" }
+								" for any workshop challenge:"
+								ul class="prizes" {
+									@for (position, grant) in grants.iter().enumerate() {
+										@let max_value: Option<i32> = grant.get("max_value");
+										@let tickets: i32 = grant.get("tickets");
+										@let min_value = position.checked_sub(1)
+											.and_then(|previous| grants[previous].get::<_, Option<i32>>("max_value"))
+											.map(|previous| previous + 1)
+											.unwrap_or(0);
+										li {
+											@match max_value {
+												Some(max_value) if min_value == 0 => { (max_value) " points or less" },
+												Some(max_value) => { (min_value) "-" (max_value) " points" },
+												None => { (min_value) "+ points" },
+											}
+											" = " (tickets) " premium ticket(s)"
+										}
+									}
+								}
+							}
+							dd {
+								"Premium tickets can be submitted in a raffle to win:"
+								ul class="prizes" {
+									@for prize in &inventory {
+										@let name: String = prize.get("name");
+										@let quantity: i32 = prize.get("quantity");
+										li { (name) @if quantity > 1 { " (" (quantity) ")" } }
+									}
+								}
+							}
+							dd { "Premium prizes will be determined at the end of the quarter through a raffle." }
+						}
+						a class="close" href="#!" { "Close" }
+					}
+					a class="modal-bg" href="#!" {}
+				}
+			}
+		}
+		@if !history.is_empty() {
+			section {
+				h2 { "Prize History" }
+				table {
+					tr {
+						th { "Prize" }
+						th { "Redeemed By" }
+						th { "Time" }
+					}
+					@for entry in &history {
+						@let prize: String = entry.get("prize");
+						@let member: Option<String> = entry.get("member");
+						@let time: DateTime<Utc> = entry.get("time");
+						tr {
+							td { (prize) }
+							td { (member.unwrap_or_default()) }
+							td { (time.format("%Y-%m-%d %H:%M UTC")) }
+						}
+					}
+				}
+			}
+		}
+		script src="/static/rewards.js" {}
+	}, client, session)
+}
+
+/// Result of redeeming a ticket; `None` when the team had too few tickets.
+fn make_redeem(prize: Option<Option<&str>>) -> Markup {
 	html! {
-		h1 { "Login" }
-		section class="login" {
-			@if let Some(error) = error { p class="error" { (error) } }
-			form method="POST" {
-				input type="text" name="name" placeholder="Team Name";
-				input type="password" name="password" placeholder="Password";
-				button type="submit" { "Log In" }
+		script src="https://ajax.googleapis.com/ajax/libs/jquery/3.5.1/jquery.min.js" {}
+		h1 { "Rewards" }
+		section class="rewards" {
+			@match prize {
+				Some(prize) => {
+					@if let Some(prize) = prize {
+						div class="gacha" {
+							div class="gacha-top" {}
+							div class="gacha-window" {
+								div style="min-height:80px;" {}
+								section class="tiles" {
+									ul {
+										@for _ in 0..7 {
+											li { a {} }
+										}
+									}
+								}
+							}
+							div class="gacha-control" {
+								div class="gacha-knob" {
+									div class="gacha-knob-turn" {}
+								}
+								div class="gacha-out" {}
+							}
+						}
+						h2 { "Congrats! You won... " (prize) }
+						p { "The Cyber Discord Bot will give you your prize" }
+					} @else {
+						p { "You've entered a ticket into the raffle!" }
+					}
+					div style="display:flex;align-items:baseline;justify-content:center" {
+						label class="switch" {
+							input type="checkbox" id="confettitoggle" checked?;
+							span class="slider round" {}
+						}
+						p { "Toggle Confetti" }
+					}
+					script src="/static/gacha.js" {}
+				},
+				None => p { "Not enough tickets." },
 			}
+			a href="/rewards" { button class="help" { "Back to Rewards" } }
 		}
 	}
 }
 
-fn get_login(client: Client, session: String) -> Result<impl Reply, Rejection> {
-	Ok(page("Login", make_login(None), client, session)?)
+/// A prize in the gacha catalog.
+struct CatalogPrize {
+	name: String,
+	weight: f64,
+	one_time: bool,
 }
 
-fn make_admin(error: Option<&str>) -> Markup {
+/// Picks a prize from the catalog for `roll` in `[0, 1)`. One-time prizes keep their share of the
+/// total weight until the team wins them, after which repeatable prizes split those odds by weight.
+fn draw_prize<'a>(catalog: &'a [CatalogPrize], owned: &[String], roll: f64) -> Option<&'a CatalogPrize> {
+	let won = |prize: &CatalogPrize| prize.one_time && owned.contains(&prize.name);
+	let total: f64 = catalog.iter().map(|prize| prize.weight).sum();
+	let repeatable: f64 = catalog.iter().filter(|prize| !prize.one_time).map(|prize| prize.weight).sum();
+	let leftover = 1.0 - catalog.iter()
+		.filter(|prize| prize.one_time && !won(prize))
+		.map(|prize| prize.weight / total)
+		.sum::<f64>();
+	let mut roll = roll;
+	let mut last = None;
+	for prize in catalog {
+		let odds = match prize.one_time {
+			true if won(prize) => 0.0,
+			true => prize.weight / total,
+			false => leftover * prize.weight / repeatable,
+		};
+		if odds > 0.0 {
+			if roll < odds {
+				return Some(prize);
+			}
+			roll -= odds;
+			last = Some(prize);
+		}
+	}
+	last
+}
+
+fn make_profile(member: Option<Row>, team: Option<Row>, members: &[Row], sessions: &[Row], tokens: &[Row], divisions: &[Row], error: Option<&str>) -> Markup {
 	html! {
-		h1 { "Admin" }
-		section {//class="login" {
+		h1 { "Profile" }
+		section class="profile" {
 			@if let Some(error) = error { p class="error" { (error) } }
-			form method="POST" {
-				input type="text" name="name" placeholder="Team Name";
-				input type="text" name="tickets" placeholder="0";
-				button type="submit" { "gib" }
+			@match member {
+				Some(member) => {
+					@let name: String = member.get("name");
+					@let discord: String = member.get("discord");
+					@let verified: bool = member.get("verified");
+					@let passwordless: bool = member.get("passwordless");
+					form method="POST" {
+						label {
+							"Username: "
+							input type="text" disabled="disabled" value=(name);
+						}
+						@if verified {
+							label {
+								"Discord (verified): "
+								input type="text" disabled="disabled" value=(discord);
+							}
+							input type="hidden" name="discord" value=(discord);
+						} @else {
+							label {
+								"Discord: "
+								input type="text" name="discord" value=(discord);
+							}
+						}
+						label {
+							"Password: "
+							input type="password" name="password" placeholder="Optional";
+						}
+						@if passwordless {
+							input type="hidden" name="current_password" value="";
+						} @else {
+							label {
+								"Current Password: "
+								input type="password" name="current_password";
+							}
+						}
+						button type="submit" { "Save" }
+					}
+					@if !verified && discord::oauth().is_some() {
+						p { a href="/login/discord" { "Verify your Discord account" } }
+					}
+					@if !divisions.is_empty() {
+						h2 { "Division" }
+						@let division: Option<String> = member.get("division");
+						@let division_name: Option<String> = member.get("division_name");
+						@let approved: bool = member.get("division_approved");
+						p {
+							@match division_name {
+								Some(division_name) => {
+									(division_name)
+									@if !approved { " (pending approval)" }
+								},
+								None => "No division",
+							}
+						}
+						form method="POST" action="/profile/division" {
+							(division_fields(divisions, division.as_deref()))
+							button type="submit" { "Change Division" }
+						}
+					}
+					h2 { "Two-Factor Authentication" }
+					@let totp_enabled: bool = member.get("totp_enabled");
+					@let totp_pending: Option<Vec<u8>> = member.get("totp_pending");
+					@if totp_enabled {
+						@let recovery: i64 = member.get("recovery");
+						p { "Enabled. " (recovery) " recovery codes remaining." }
+						form method="POST" action="/profile/totp" {
+							input type="text" name="code" placeholder="Authentication code" autocomplete="one-time-code";
+							button type="submit" name="action" value="recovery" { "New Recovery Codes" }
+							button type="submit" name="action" value="disable" { "Disable" }
+						}
+					} @else if let Some(secret) = totp_pending {
+						@let issuer: String = member.get("issuer");
+						p {
+							"Add this key to your authenticator app, or open "
+							a href=(totp::url(&issuer, &name, &secret)) { "this link" }
+							" on your phone, then enter the code it shows."
+						}
+						p { code { (totp::encode(&secret)) } }
+						form method="POST" action="/profile/totp" {
+							input type="hidden" name="action" value="confirm";
+							input type="text" name="code" placeholder="Authentication code" autocomplete="one-time-code";
+							button type="submit" { "Enable" }
+						}
+					} @else {
+						form method="POST" action="/profile/totp" {
+							input type="hidden" name="action" value="begin";
+							button type="submit" { "Set Up Two-Factor Authentication" }
+						}
+					}
+					h2 { "Team" }
+					@match team {
+						Some(team) => {
+							@let team_name: String = team.get("name");
+							@let invite: String = team.get("invite");
+							@let captain: bool = team.get("captain");
+							p { "Team Name: " (team_name) }
+							p { "Invite Code: " code { (invite) } }
+							table class="members" {
+								tbody {
+									@for row in members {
+										@let id: i32 = row.get("id");
+										@let member_name: String = row.get("name");
+										@let member_captain: bool = row.get("captain");
+										@let current: bool = row.get("current");
+										tr {
+											td { (member_name) }
+											td { @if member_captain { "Captain" } }
+											td {
+												@if captain && !current {
+													form method="POST" action="/profile/team" {
+														input type="hidden" name="action" value="kick";
+														input type="hidden" name="member" value=(id);
+														button type="submit" { "Kick" }
+													}
+												}
+											}
+										}
+									}
+								}
+							}
+							@if captain {
+								form method="POST" action="/profile/team" {
+									input type="hidden" name="action" value="invite";
+									button type="submit" { "New Invite Code" }
+								}
+							}
+							form method="POST" action="/profile/team" {
+								input type="hidden" name="action" value="leave";
+								button type="submit" { "Leave Team" }
+							}
+						},
+						None => {
+							form method="POST" action="/profile/team" {
+								input type="hidden" name="action" value="create";
+								input type="text" name="name" placeholder="Team Name" maxlength="64" pattern="[ -~]+";
+								button type="submit" { "Create Team" }
+							}
+							form method="POST" action="/profile/team" {
+								input type="hidden" name="action" value="join";
+								input type="text" name="invite" placeholder="Invite Code";
+								button type="submit" { "Join Team" }
+							}
+						}
+					}
+					h2 { "Sessions" }
+					table class="sessions" {
+						thead {
+							tr {
+								th { "Device" }
+								th { "Signed In" }
+								th { "Last Seen" }
+								th {}
+							}
+						}
+						tbody {
+							@for row in sessions {
+								@let id: i32 = row.get("id");
+								@let agent: Option<String> = row.get("agent");
+								@let created: DateTime<Utc> = row.get("created");
+								@let seen: DateTime<Utc> = row.get("seen");
+								@let current: bool = row.get("current");
+								tr {
+									td { (agent.unwrap_or_else(|| "Unknown".to_string())) }
+									td { (created.format("%Y-%m-%d %H:%M UTC")) }
+									td { (seen.format("%Y-%m-%d %H:%M UTC")) }
+									td {
+										@if current {
+											"This session"
+										} @else {
+											form method="POST" action="/profile/sessions" {
+												input type="hidden" name="id" value=(id);
+												button type="submit" { "Revoke" }
+											}
+										}
+									}
+								}
+							}
+						}
+					}
+					form method="POST" action="/profile/sessions" {
+						input type="hidden" name="all" value="true";
+						button type="submit" { "Log Out Everywhere" }
+					}
+					h2 { "API Tokens" }
+					p { "Tokens let scripts submit flags and read challenges and the scoreboard with an " code { "Authorization: Bearer" } " header." }
+					table class="sessions" {
+						tbody {
+							@for row in tokens {
+								@let id: i32 = row.get("id");
+								@let token_name: String = row.get("name");
+								@let created: DateTime<Utc> = row.get("created");
+								tr {
+									td { (token_name) }
+									td { (created.format("%Y-%m-%d %H:%M UTC")) }
+									td {
+										form method="POST" action="/profile/tokens" {
+											input type="hidden" name="action" value="revoke";
+											input type="hidden" name="id" value=(id);
+											button type="submit" { "Revoke" }
+										}
+									}
+								}
+							}
+						}
+					}
+					form method="POST" action="/profile/tokens" {
+						input type="hidden" name="action" value="create";
+						input type="text" name="name" placeholder="Token Name" maxlength="64";
+						button type="submit" { "Create Token" }
+					}
+				},
+				None => {
+					p class="not-logged-in" { "Log in to view your profile." }
+				}
 			}
 		}
 	}
 }
 
-fn get_admin(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
-	let status: bool = result! (client.query("SELECT isAdmin from scrap.team 
-	inner join scrap.session on scrap.team.id = scrap.session.team 
-	where scrap.session.cookie=$1",
-		&[&session]))[0].get("isAdmin");
-	if status {
-		Ok(page("Admin", make_admin(None), client, session)?)
-	}
-	else {
-		Ok(page("Login", make_login(None), client, session)?)
-	}
+fn get_sessions(client: &mut Client, session: &str) -> Result<Vec<Row>, Rejection> {
+	Ok(result!(client.query("SELECT id, agent, created, seen, cookie=$1 AS current
+		FROM scrap.session
+		WHERE member=lookup_member($1) AND NOT expired(created, seen)
+		ORDER BY seen DESC",
+		&[&session])))
+}
+
+fn profile_body(mut client: Client, session: String, error: Option<&str>) -> Result<String, Rejection> {
+	let member = result!(client.query("SELECT name, discord,
+		discord_id IS NOT NULL AS verified, hash IS NULL AS passwordless,
+		totp IS NOT NULL AS totp_enabled, totp_pending,
+		(SELECT COUNT(*) FROM scrap.recovery WHERE member=member.id) AS recovery,
+		(SELECT title FROM scrap.ctf WHERE current) AS issuer,
+		division, division_approved,
+		(SELECT name FROM scrap.division WHERE slug=member.division) AS division_name
+		FROM scrap.member member
+		WHERE id=lookup_member($1)",
+		&[&session])).pop();
+	let team = result!(client.query("SELECT team.name, team.invite, member.captain
+		FROM scrap.team team
+		JOIN scrap.member member ON member.team=team.id
+		WHERE member.id=lookup_member($1)",
+		&[&session])).pop();
+	let members = result!(client.query("SELECT id, name, captain, id=lookup_member($1) AS current
+		FROM scrap.member
+		WHERE team=lookup($1)
+		ORDER BY captain DESC, joined ASC NULLS FIRST, id ASC",
+		&[&session]));
+	let sessions = get_sessions(&mut client, &session)?;
+	let tokens = result!(client.query("SELECT id, name, created
+		FROM scrap.token
+		WHERE member=lookup_member($1)
+		ORDER BY created DESC",
+		&[&session]));
+	let divisions = get_divisions(&mut client)?;
+	let adjustments = result!(client.query("SELECT delta, reason, time
+		FROM scrap.adjustment
+		WHERE team=lookup($1) AND ctf=current_ctf()
+		ORDER BY time DESC",
+		&[&session]));
+	make_body("Profile", html! {
+		(make_profile(member, team, &members, &sessions, &tokens, &divisions, error))
+		@if !adjustments.is_empty() {
+			section class="profile" {
+				h2 { "Score Adjustments" }
+				(make_adjustments(&adjustments, false))
+			}
+		}
+	}, client, session)
+}
+
+fn make_adjustments(adjustments: &[Row], admin: bool) -> Markup {
+	html! {
+		table class="sessions" {
+			tbody {
+				@for row in adjustments {
+					@let delta: i32 = row.get("delta");
+					@let reason: String = row.get("reason");
+					@let time: DateTime<Utc> = row.get("time");
+					tr {
+						td { (time.format("%Y-%m-%d %H:%M UTC")) }
+						@if admin {
+							@let team: String = row.get("team");
+							td { (team) }
+						}
+						td { @if delta > 0 { "+" } (delta) }
+						td { (reason) }
+						@if admin {
+							@let id: i32 = row.get("id");
+							td {
+								form method="POST" action="/admin/adjustments" {
+									input type="hidden" name="action" value="delete";
+									input type="hidden" name="id" value=(id);
+									button type="submit" { "Remove" }
+								}
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+}
+
+fn get_profile(client: Client, session: String) -> Result<impl Reply, Rejection> {
+	Ok(make_reply(profile_body(client, session, None)?))
+}
+
+fn make_recovery(codes: &[String]) -> Markup {
+	html! {
+		h1 { "Recovery Codes" }
+		section class="profile" {
+			p { "Each code can be used once instead of an authentication code. Store them somewhere safe; they will not be shown again." }
+			ul { @for code in codes { li { code { (code) } } } }
+			p { a href="/profile" { "Back to Profile" } }
+		}
+	}
+}
+
+fn make_token(name: &str, token: &str) -> Markup {
+	html! {
+		h1 { "API Token" }
+		section class="profile" {
+			p { "Copy the token for " (name) " now; it will not be shown again." }
+			p { code { (token) } }
+			p { "Submit a flag with:" }
+			pre { code { "curl -H \"Authorization: Bearer " (token) "\" -d \"slug=<challenge>&flag=<flag>\" <site>/challenges.json" } }
+			p { a href="/profile" { "Back to Profile" } }
+		}
+	}
+}
+
+fn division_fields(divisions: &[Row], current: Option<&str>) -> Markup {
+	html! {
+		label {
+			"Division: "
+			select name="division" {
+				option value="" { "No division" }
+				@for division in divisions {
+					@let slug: String = division.get("slug");
+					@let name: String = division.get("name");
+					@let email_domain: Option<String> = division.get("email_domain");
+					@let approval: bool = division.get("approval");
+					option value=(slug) selected?[current == Some(slug.as_str())] {
+						(name)
+						@if let Some(domain) = email_domain { " (@" (domain) " email)" }
+						@if approval { " (requires approval)" }
+					}
+				}
+			}
+		}
+		input type="email" name="email" placeholder="Email (if your division requires one)";
+	}
+}
+
+fn get_divisions(client: &mut Client) -> Result<Vec<Row>, Rejection> {
+	Ok(result!(client.query("SELECT slug, name, email_domain, approval
+		FROM scrap.division
+		ORDER BY position ASC",
+		&[])))
+}
+
+/// Checks a division choice against its email domain, returning whether it needs admin approval.
+fn check_division(client: &mut Client, division: &str, email: &str) -> Result<Result<bool, &'static str>, Rejection> {
+	if division.is_empty() {
+		return Ok(Ok(false));
+	}
+	let division = match result!(client.query("SELECT email_domain, approval FROM scrap.division
+		WHERE slug=$1",
+		&[&division])).pop() {
+		Some(division) => division,
+		None => return Ok(Err("Unknown division.")),
+	};
+	if let Some(domain) = division.get::<_, Option<String>>("email_domain") {
+		if !email.to_lowercase().ends_with(&format!("@{}", domain.to_lowercase())) {
+			return Ok(Err("That division requires an email address from its domain."));
+		}
+	}
+	Ok(Ok(division.get("approval")))
+}
+
+fn make_register(divisions: &[Row], error: Option<&str>) -> Markup {
+	html! {
+		h1 { "Register" }
+		section class="register" {
+			@if let Some(error) = error { p class="error" { (error) } }
+			form method="POST" {
+				input type="text" name="name" placeholder="Username" maxlength="64" pattern="[ -~]+";
+				input type="text" name="discord" placeholder="Discord username";
+				input type="password" name="password" placeholder="Password";
+				@if !divisions.is_empty() {
+					(division_fields(divisions, None))
+				}
+				button type="submit" { "Register" }
+			}
+			@if discord::oauth().is_some() {
+				p { a href="/login/discord" { "Register with Discord" } }
+			}
+		}
+	}
+}
+
+fn get_register(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+	let divisions = get_divisions(&mut client)?;
+	page("Register", make_register(&divisions, None), client, session)
+}
+
+fn make_login(error: Option<&str>) -> Markup {
+	html! {
+		h1 { "Login" }
+		section class="login" {
+			@if let Some(error) = error { p class="error" { (error) } }
+			form method="POST" {
+				input type="text" name="name" placeholder="Username";
+				input type="password" name="password" placeholder="Password";
+				button type="submit" { "Log In" }
+			}
+			@if discord::oauth().is_some() {
+				p { a href="/login/discord" { "Log in with Discord" } }
+			}
+		}
+	}
+}
+
+fn get_login(client: Client, session: String) -> Result<impl Reply, Rejection> {
+	Ok(page("Login", make_login(None), client, session)?)
+}
+
+fn make_login_totp(error: Option<&str>) -> Markup {
+	html! {
+		h1 { "Two-Factor Authentication" }
+		section class="login" {
+			@if let Some(error) = error { p class="error" { (error) } }
+			form method="POST" {
+				input type="text" name="code" placeholder="Authentication code" autocomplete="one-time-code";
+				button type="submit" { "Verify" }
+			}
+			p { "Lost your device? Enter one of your recovery codes instead." }
+		}
+	}
+}
+
+fn get_login_totp(client: Client, session: String) -> Result<impl Reply, Rejection> {
+	page("Login", make_login_totp(None), client, session)
+}
+
+fn make_admin(error: Option<&str>) -> Markup {
+	html! {
+		h1 { "Admin" }
+		section {//class="login" {
+			@if let Some(error) = error { p class="error" { (error) } }
+			form method="POST" {
+				input type="text" name="name" placeholder="Team Name";
+				input type="text" name="tickets" placeholder="Premium Tickets";
+				input type="text" name="challenge" placeholder="or Writeup Challenge Slug";
+				button type="submit" { "gib" }
+			}
+			form method="POST" action="/admin/reset" {
+				input type="text" name="name" placeholder="Username";
+				button type="submit" { "Reset Password" }
+			}
+			form method="POST" action="/admin/adjustments" {
+				input type="hidden" name="action" value="create";
+				input type="text" name="name" placeholder="Team Name";
+				input type="text" name="delta" placeholder="Points, eg. 50 or -100";
+				input type="text" name="reason" placeholder="Reason";
+				button type="submit" { "Adjust Score" }
+			}
+			form method="POST" action="/admin/announcements" {
+				input type="hidden" name="action" value="create";
+				input type="text" name="message" placeholder="Announcement";
+				input type="text" name="challenge" placeholder="Challenge slug (optional)";
+				button type="submit" { "Announce" }
+			}
+			form method="POST" action="/admin/inventory" {
+				input type="text" name="name" placeholder="Raffle Prize";
+				input type="text" name="quantity" placeholder="Quantity";
+				button type="submit" { "Set Inventory" }
+			}
+			form method="POST" action="/admin/raffle" {
+				button type="submit" { "Draw Raffle" }
+			}
+			form method="POST" action="/admin/team" {
+				input type="text" name="name" placeholder="Team Name";
+				select name="state" {
+					option value="visible" { "Visible" }
+					option value="hidden" { "Hidden" }
+					option value="banned" { "Banned" }
+				}
+				input type="text" name="reason" placeholder="Reason";
+				button type="submit" { "Set Team State" }
+			}
+		}
+	}
+}
+
+/// Admins without two-factor authentication lose access when `require_admin_totp` is set.
+fn is_admin(client: &mut Client, session: &str) -> Result<bool, Rejection> {
+	Ok(result!(client.query("SELECT isadmin AND (totp IS NOT NULL OR NOT require_admin_totp) AS isadmin
+		FROM scrap.member, scrap.ctf
+		WHERE member.id=lookup_member($1) AND ctf.current",
+		&[&session])).pop().map(|member| member.get("isadmin")).unwrap_or(false))
+}
+
+fn get_admin(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+	if is_admin(&mut client, &session)? {
+		let pending = result!(client.query("SELECT member.id, member.name, member.email, division.name AS division
+			FROM scrap.member member
+			JOIN scrap.division division ON division.slug=member.division
+			WHERE NOT member.division_approved
+			ORDER BY member.id ASC",
+			&[]));
+		let adjustments = result!(client.query("SELECT adjustment.id, adjustment.delta, adjustment.reason, adjustment.time,
+			team.name AS team
+			FROM scrap.adjustment adjustment
+			JOIN scrap.team team ON team.id=adjustment.team
+			WHERE adjustment.ctf=current_ctf()
+			ORDER BY adjustment.time DESC",
+			&[]));
+		let announcements = result!(client.query("SELECT announcement.id, announcement.message, announcement.time, challenge.slug
+			FROM scrap.announcement announcement
+			LEFT JOIN scrap.challenge challenge ON challenge.id=announcement.challenge
+			WHERE announcement.ctf=current_ctf()
+			ORDER BY announcement.time DESC
+			LIMIT 20",
+			&[]));
+		let inventory = result!(client.query("SELECT inventory.name, inventory.quantity,
+			(SELECT COUNT(*) FROM scrap.winner winner WHERE winner.inventory=inventory.id) AS awarded
+			FROM scrap.inventory inventory
+			ORDER BY inventory.id ASC",
+			&[]));
+		let drawings = result!(client.query("SELECT drawing.id, drawing.seed, drawing.time,
+			cardinality(drawing.entries) AS entries, member.name AS admin,
+			(SELECT COUNT(*) FROM scrap.winner winner WHERE winner.drawing=drawing.id) AS winners
+			FROM scrap.drawing drawing
+			LEFT JOIN scrap.member member ON member.id=drawing.admin
+			ORDER BY drawing.time DESC",
+			&[]));
+		let unfulfilled = result!(client.query("SELECT prize.kind, prize.id, prize.prize, team.name AS team,
+			member.name AS member, member.discord, prize.time
+			FROM (
+				SELECT 'raffle' AS kind, winner.id, inventory.name AS prize, raffle.team, raffle.member, drawing.time
+				FROM scrap.winner winner
+				JOIN scrap.raffle raffle ON raffle.id=winner.entry
+				JOIN scrap.inventory inventory ON inventory.id=winner.inventory
+				JOIN scrap.drawing drawing ON drawing.id=winner.drawing
+				WHERE winner.fulfilled IS NULL
+				UNION ALL
+				SELECT 'draw', id, prize, team, member, time
+				FROM scrap.draw
+				WHERE fulfilled IS NULL
+			) prize
+			JOIN scrap.team team ON team.id=prize.team
+			LEFT JOIN scrap.member member ON member.id=prize.member
+			ORDER BY prize.time ASC",
+			&[]));
+		let audit = result!(client.query("SELECT audit.time, audit.action, audit.reason,
+			member.name AS admin, team.name AS team
+			FROM scrap.audit audit
+			LEFT JOIN scrap.member member ON member.id=audit.admin
+			LEFT JOIN scrap.team team ON team.id=audit.team
+			ORDER BY audit.time DESC
+			LIMIT 50",
+			&[]));
+		return page("Admin", html! {
+			(make_admin(None))
+			@if !pending.is_empty() {
+				section {
+					h2 { "Division Approvals" }
+					table {
+						tbody {
+							@for row in &pending {
+								@let id: i32 = row.get("id");
+								@let name: String = row.get("name");
+								@let email: Option<String> = row.get("email");
+								@let division: String = row.get("division");
+								tr {
+									td { (name) }
+									td { (email.unwrap_or_default()) }
+									td { (division) }
+									td {
+										form method="POST" action="/admin/division" {
+											input type="hidden" name="member" value=(id);
+											button type="submit" name="action" value="approve" { "Approve" }
+											button type="submit" name="action" value="reject" { "Reject" }
+										}
+									}
+								}
+							}
+						}
+					}
+				}
+			}
+			@if !announcements.is_empty() {
+				section {
+					h2 { "Announcements" }
+					table {
+						tbody {
+							@for row in &announcements {
+								@let id: i32 = row.get("id");
+								@let message: String = row.get("message");
+								@let time: DateTime<Utc> = row.get("time");
+								@let slug: Option<String> = row.get("slug");
+								tr {
+									td { (time.format("%Y-%m-%d %H:%M UTC")) }
+									td { (slug.unwrap_or_default()) }
+									td { (message) }
+									td {
+										form method="POST" action="/admin/announcements" {
+											input type="hidden" name="action" value="delete";
+											input type="hidden" name="id" value=(id);
+											button type="submit" { "Delete" }
+										}
+									}
+								}
+							}
+						}
+					}
+				}
+			}
+			@if !inventory.is_empty() {
+				section {
+					h2 { "Raffle Prizes" }
+					table {
+						tbody {
+							@for row in &inventory {
+								@let name: String = row.get("name");
+								@let quantity: i32 = row.get("quantity");
+								@let awarded: i64 = row.get("awarded");
+								tr {
+									td { (name) }
+									td { (awarded) " of " (quantity) " awarded" }
+								}
+							}
+						}
+					}
+				}
+			}
+			@if !drawings.is_empty() {
+				section {
+					h2 { "Raffle Drawings" }
+					table {
+						tbody {
+							@for row in &drawings {
+								@let time: DateTime<Utc> = row.get("time");
+								@let seed: String = row.get("seed");
+								@let entries: Option<i32> = row.get("entries");
+								@let winners: i64 = row.get("winners");
+								@let admin: Option<String> = row.get("admin");
+								@let id: i32 = row.get("id");
+								tr {
+									td { (time.format("%Y-%m-%d %H:%M UTC")) }
+									td { (admin.unwrap_or_default()) }
+									td { code { (seed) } }
+									td { (winners) " winners from " (entries.unwrap_or(0)) " entries" }
+									td {
+										form method="POST" action="/admin/raffle/verify" {
+											input type="hidden" name="id" value=(id);
+											button type="submit" { "Verify" }
+										}
+									}
+								}
+							}
+						}
+					}
+				}
+			}
+			section {
+				h2 { "Unfulfilled Prizes" }
+				p { a href="/admin/winners.csv" { "Export all prizes as CSV" } }
+				@if !unfulfilled.is_empty() {
+					table {
+						tbody {
+							@for row in &unfulfilled {
+								@let kind: String = row.get("kind");
+								@let id: i32 = row.get("id");
+								@let prize: String = row.get("prize");
+								@let team: String = row.get("team");
+								@let member: Option<String> = row.get("member");
+								@let discord: Option<String> = row.get("discord");
+								@let time: DateTime<Utc> = row.get("time");
+								tr {
+									td { (time.format("%Y-%m-%d %H:%M UTC")) }
+									td { (prize) }
+									td { (team) }
+									td { (member.unwrap_or_default()) }
+									td { (discord.unwrap_or_default()) }
+									td {
+										form method="POST" action="/admin/prizes" {
+											input type="hidden" name="kind" value=(kind);
+											input type="hidden" name="id" value=(id);
+											button type="submit" { "Fulfilled" }
+										}
+									}
+								}
+							}
+						}
+					}
+				}
+			}
+			@if !adjustments.is_empty() {
+				section {
+					h2 { "Score Adjustments" }
+					(make_adjustments(&adjustments, true))
+				}
+			}
+			@if !audit.is_empty() {
+				section {
+					h2 { "Audit Log" }
+					table {
+						tbody {
+							@for row in &audit {
+								@let time: DateTime<Utc> = row.get("time");
+								@let action: String = row.get("action");
+								@let reason: Option<String> = row.get("reason");
+								@let admin: Option<String> = row.get("admin");
+								@let team: Option<String> = row.get("team");
+								tr {
+									td { (time.format("%Y-%m-%d %H:%M UTC")) }
+									td { (admin.unwrap_or_default()) }
+									td { (team.unwrap_or_default()) }
+									td { (action) }
+									td { (reason.unwrap_or_default()) }
+								}
+							}
+						}
+					}
+				}
+			}
+		}, client, session);
+	}
+	let isadmin = result!(client.query("SELECT isadmin FROM scrap.member
+		WHERE id=lookup_member($1)",
+		&[&session])).pop().map(|member| member.get("isadmin")).unwrap_or(false);
+	if isadmin {
+		Ok(page("Admin", html! {
+			h1 { "Admin" }
+			section {
+				p class="error" { "Set up two-factor authentication on your " a href="/profile" { "profile" } " to use the admin panel." }
+			}
+		}, client, session)?)
+	}
+	else {
+		Ok(page("Login", make_login(None), client, session)?)
+	}
+}
+
+fn make_reset(valid: bool, error: Option<&str>) -> Markup {
+	html! {
+		h1 { "Reset Password" }
+		section class="login" {
+			@if let Some(error) = error { p class="error" { (error) } }
+			@if valid {
+				form method="POST" {
+					input type="password" name="password" placeholder="New Password";
+					button type="submit" { "Reset Password" }
+				}
+			} @else {
+				p { "This reset link is invalid or has expired." }
+			}
+		}
+	}
+}
+
+fn get_reset(mut client: Client, session: String, token: String) -> Result<impl Reply, Rejection> {
+	let valid = !result!(client.query("SELECT 1 FROM scrap.reset
+		WHERE hash=encode(digest($1, 'sha256'), 'hex') AND expires > NOW()",
+		&[&token])).is_empty();
+	page("Reset Password", make_reset(valid, None), client, session)
+}
+
+fn get_static(static_path: &Path, tail: Peek, if_none_match: Option<String>, file: File) -> warp::reply::Response {
+	let relative = PathBuf::from(percent_decode_str(tail.as_str()).decode_utf8_lossy().as_ref());
+	// Challenge files live under a content hash, so their URL changes whenever they do.
+	let cache_control = if relative.starts_with("files") {
+		"public, max-age=31536000, immutable"
+	} else {
+		"public, no-cache"
+	};
+	let etag = fs::metadata(static_path.join(&relative)).ok()
+		.and_then(|metadata| Some((metadata.len(), metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?)))
+		.map(|(len, modified)| format!("\"{:x}-{:x}\"", len, modified.as_secs()));
+	let mut response = match (&etag, if_none_match) {
+		(Some(etag), Some(tags)) if tags.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*") => {
+			reply::with_status(reply::reply(), StatusCode::NOT_MODIFIED).into_response()
+		},
+		_ => file.into_response(),
+	};
+	let headers = response.headers_mut();
+	headers.insert("cache-control", cache_control.parse().unwrap());
+	if let Some(etag) = etag.and_then(|etag| etag.parse().ok()) {
+		headers.insert("etag", etag);
+	}
+	response
+}
+
+fn error(err: Rejection) -> Result<impl Reply, Rejection> {
+	match err.status() {
+		StatusCode::METHOD_NOT_ALLOWED => {
+			Ok(Response::builder()
+				.status(StatusCode::NOT_FOUND)
+				.body("404 Page Not Found"))
+		},
+		_ => {
+			Ok(Response::builder()
+				.status(StatusCode::INTERNAL_SERVER_ERROR)
+				.body("500 Internal Server Error"))
+		}
+	}
+}
+
+/// Recounts solves per challenge, leaving out hidden and banned teams.
+fn count_solves(transaction: &mut Transaction) -> Result<(), Rejection> {
+	result!(transaction.execute("UPDATE scrap.challenge challenge
+		SET solves=(SELECT COUNT(*) FROM scrap.solve solve
+		JOIN scrap.team team ON team.id=solve.team
+		WHERE solve.challenge=challenge.id AND NOT team.hidden AND NOT team.banned)
+		WHERE challenge.ctf=current_ctf()",
+		&[]));
+	Ok(())
+}
+
+/// Recomputes every team's score from its solves and manual adjustments in the current CTF.
+fn update_scores(transaction: &mut Transaction) -> Result<(), Rejection> {
+	result!(transaction.execute("UPDATE scrap.team team
+		SET score=COALESCE((SELECT SUM(challenge.value)
+		FROM scrap.solve solve
+		JOIN scrap.challenge challenge ON challenge.id=solve.challenge
+		WHERE solve.team=team.id AND solve.ctf=current_ctf()), 0)
+		+ COALESCE((SELECT SUM(adjustment.delta)
+		FROM scrap.adjustment adjustment
+		WHERE adjustment.team=team.id AND adjustment.ctf=current_ctf()), 0)",
+		&[]));
+	Ok(())
+}
+
+#[derive(Serialize)]
+struct Solve {
+	team: String,
+	challenge: String,
+	time: String,
+}
+
+/// Tells live scoreboards and webhooks about a solve, unless the team is hidden from rankings.
+fn publish_solve(client: &mut Client, hub: &Hub, webhooks: &Webhooks, session: &str, slug: &str, first_blood: bool) {
+	let solve = match client.query("SELECT team.name, member.name AS member, challenge.title, challenge.value, solve.time
+		FROM scrap.solve solve
+		JOIN scrap.team team ON team.id=solve.team
+		JOIN scrap.challenge challenge ON challenge.id=solve.challenge
+		LEFT JOIN scrap.member member ON member.id=solve.member
+		WHERE solve.team=lookup($1) AND challenge.slug=$2 AND challenge.ctf=current_ctf() AND NOT team.hidden",
+		&[&session, &slug]) {
+		Ok(mut solves) => solves.pop(),
+		Err(e) => {
+			eprintln!("Failed to publish solve: {}", e);
+			None
+		},
+	};
+	if let Some(solve) = solve {
+		let time: DateTime<Utc> = solve.get("time");
+		let team: String = solve.get("name");
+		let member: Option<String> = solve.get("member");
+		let challenge: String = solve.get("title");
+		let value: i32 = solve.get("value");
+		hub.publish("solve", &Solve {
+			team: team.clone(),
+			challenge: challenge.clone(),
+			time: time.format("%H:%M:%S").to_string(),
+		});
+		webhooks.send(if first_blood {
+			Event::FirstBlood { team, member, challenge, value }
+		} else {
+			Event::Solve { team, member, challenge, value }
+		});
+	}
+	publish_scoreboard(client, hub);
+}
+
+/// What happened to a flag submission.
+#[derive(Clone, Copy, PartialEq)]
+enum Outcome {
+	Correct,
+	/// Correct, but submitted after the CTF ended, so only recorded as practice.
+	Practiced,
+	Incorrect,
+	AlreadySolved,
+	NotStarted,
+	NotLoggedIn,
+	NoTeam,
+	Banned,
+	NoSuchChallenge,
+	/// The team submitted too many wrong flags in the last minute, so this one was not checked.
+	RateLimited,
+}
+
+impl Outcome {
+	const ALL: [Outcome; 10] = [Outcome::Correct, Outcome::Practiced, Outcome::Incorrect, Outcome::AlreadySolved,
+		Outcome::NotStarted, Outcome::NotLoggedIn, Outcome::NoTeam, Outcome::Banned, Outcome::NoSuchChallenge,
+		Outcome::RateLimited];
+
+	/// Name used in the `submission` cookie and the API.
+	fn code(self) -> &'static str {
+		match self {
+			Outcome::Correct => "correct",
+			Outcome::Practiced => "practiced",
+			Outcome::Incorrect => "incorrect",
+			Outcome::AlreadySolved => "already_solved",
+			Outcome::NotStarted => "not_started",
+			Outcome::NotLoggedIn => "not_logged_in",
+			Outcome::NoTeam => "no_team",
+			Outcome::Banned => "banned",
+			Outcome::NoSuchChallenge => "no_such_challenge",
+			Outcome::RateLimited => "rate_limited",
+		}
+	}
+
+	fn parse(code: &str) -> Option<Outcome> {
+		Outcome::ALL.iter().copied().find(|outcome| outcome.code() == code)
+	}
+
+	fn message(self) -> &'static str {
+		match self {
+			Outcome::Correct => "Correct!",
+			Outcome::Practiced => "Correct! The CTF has ended, so this solve was recorded as practice and not scored.",
+			Outcome::Incorrect => "Incorrect flag.",
+			Outcome::AlreadySolved => "Your team has already solved this challenge.",
+			Outcome::NotStarted => "The CTF has not started yet.",
+			Outcome::NotLoggedIn => "Log in to submit flags.",
+			Outcome::NoTeam => "Join a team from your profile to submit flags.",
+			Outcome::Banned => "Your team has been disqualified and can no longer submit flags.",
+			Outcome::NoSuchChallenge => "No such challenge.",
+			Outcome::RateLimited => "Too many incorrect flags, try again in a minute.",
+		}
+	}
+
+	fn correct(self) -> bool {
+		self == Outcome::Correct || self == Outcome::Practiced
+	}
+
+	fn status(self) -> StatusCode {
+		match self {
+			Outcome::NotLoggedIn => StatusCode::UNAUTHORIZED,
+			Outcome::NotStarted | Outcome::NoTeam | Outcome::Banned => StatusCode::FORBIDDEN,
+			Outcome::NoSuchChallenge => StatusCode::NOT_FOUND,
+			Outcome::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+			_ => StatusCode::OK,
+		}
+	}
+
+	/// Flash message for the outcome of a submission to `slug`.
+	fn flash(self, slug: &str) -> Markup {
+		html! {
+			@if self.correct() {
+				p.flash { (slug) ": " (self.message()) }
+			} @else {
+				p class="error flash" { (slug) ": " (self.message()) }
+			}
+		}
+	}
+}
+
+/// Checks a flag for the session's team, recording a scored solve, or a practice solve once the CTF
+/// is over.
+fn submit_flag(client: &mut Client, session: &str, hub: &Hub, webhooks: &Webhooks, slug: &str, flag: &str) -> Result<Outcome, Rejection> {
+	let now = Utc::now();
+	let ctf = &result!(client.query("SELECT start, stop FROM scrap.ctf WHERE current", &[]))[0];
+	if ctf.try_get("start").map(|start: DateTime<Utc>| now < start).unwrap_or(false) {
+		return Ok(Outcome::NotStarted);
+	}
+	let practice = ctf.try_get("stop").map(|stop: DateTime<Utc>| now > stop).unwrap_or(false);
+	let member = result!(client.query("SELECT member.team, team.banned FROM scrap.member member
+		LEFT JOIN scrap.team team ON team.id=member.team
+		WHERE member.id=lookup_member($1)",
+		&[&session])).pop();
+	let member = match member {
+		Some(member) => member,
+		None => return Ok(Outcome::NotLoggedIn),
+	};
+	let team: Option<i32> = member.get("team");
+	let team = match team {
+		Some(team) => team,
+		None => return Ok(Outcome::NoTeam),
+	};
+	if member.get::<_, bool>("banned") {
+		return Ok(Outcome::Banned);
+	}
+	let challenge = match result!(client.query("SELECT challenge.id, flag,
+		solved(team.id, challenge.id) AS solved,
+		EXISTS (SELECT FROM scrap.practice practice
+			WHERE practice.team=team.id AND practice.challenge=challenge.id) AS practiced
+		FROM scrap.challenge challenge, scrap.team team
+		WHERE slug=$1 AND enabled=true AND ctf=current_ctf() AND team.id=$2",
+		&[&slug, &team])).pop() {
+		Some(challenge) => challenge,
+		None => return Ok(Outcome::NoSuchChallenge),
+	};
+	if challenge.get::<_, bool>("solved") || (practice && challenge.get::<_, bool>("practiced")) {
+		return Ok(Outcome::AlreadySolved);
+	}
+	let id: i32 = challenge.get("id");
+	// A team guessing in a loop gets ten wrong flags a minute; past that, flags are not checked at all.
+	if !practice && result!(client.query_one("SELECT COUNT(*) FROM scrap.submission
+		WHERE team=$1 AND time > NOW() - INTERVAL '1 minute'",
+		&[&team])).get::<_, i64>(0) >= 10 {
+		return Ok(Outcome::RateLimited);
+	}
+	if challenge.get::<_, String>("flag") != flag {
+		// Practice guesses are not logged.
+		if !practice {
+			result!(client.execute("INSERT INTO scrap.submission (team, member, challenge, flag)
+				VALUES ($1, lookup_member($2), $3, $4)",
+				&[&team, &session, &id, &flag]));
+		}
+		return Ok(Outcome::Incorrect);
+	}
+	if practice {
+		// Practice solves never touch the scoreboard.
+		result!(client.execute("INSERT INTO scrap.practice (team, member, challenge)
+			VALUES ($1, lookup_member($2), $3)
+			ON CONFLICT DO NOTHING",
+			&[&team, &session, &id]));
+		return Ok(Outcome::Practiced);
+	}
+	let mut transaction = result!(client.transaction());
+	// Locking the challenge makes simultaneous solves take turns, so only one of them sees no earlier solves.
+	let first_blood: bool = result!(transaction.query_one("SELECT NOT EXISTS (SELECT FROM scrap.solve solve
+		JOIN scrap.team team ON team.id=solve.team
+		WHERE solve.challenge=challenge.id AND NOT team.hidden AND NOT team.banned) AS first_blood
+		FROM scrap.challenge challenge
+		WHERE challenge.id=$1
+		FOR UPDATE OF challenge",
+		&[&id])).get("first_blood");
+	let rows = result!(transaction.execute("INSERT INTO scrap.solve (team, member, challenge)
+		VALUES ($1, lookup_member($2), $3)
+		ON CONFLICT DO NOTHING",
+		&[&team, &session, &id]));
+	if rows == 0 {
+		// Another member got there first.
+		return Ok(Outcome::AlreadySolved);
+	}
+	result!(transaction.execute("UPDATE scrap.team SET submit=NOW() WHERE id=$1", &[&team]));
+	count_solves(&mut transaction)?;
+	update_scores(&mut transaction)?;
+	result!(transaction.commit());
+	publish_solve(client, hub, webhooks, session, slug, first_blood);
+	Ok(Outcome::Correct)
+}
+
+fn submit(mut client: Client, session: String, hub: Arc<Hub>, webhooks: Arc<Webhooks>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let empty = String::new();
+	let slug = form.get("slug").unwrap_or(&empty);
+	let flag = form.get("flag").unwrap_or(&empty);
+	let outcome = submit_flag(&mut client, &session, &hub, &webhooks, slug, flag)?;
+	// The slug is whatever was posted, so it is encoded before going into headers.
+	let slug = utf8_percent_encode(slug, NON_ALPHANUMERIC);
+	let location = if outcome.correct() { "/challenges".to_string() } else { format!("/challenges#{}", slug) };
+	Ok(Response::builder()
+		.header("location", location)
+		.header("set-cookie", format!("submission={}:{}; HttpOnly; SameSite=Lax", outcome.code(), slug))
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+#[derive(Serialize)]
+struct Submission {
+	slug: String,
+	result: &'static str,
+	correct: bool,
+	message: &'static str,
+}
+
+/// Flag submission for scripts, answering with the outcome as JSON.
+fn submit_json(mut client: Client, session: String, hub: Arc<Hub>, webhooks: Arc<Webhooks>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let empty = String::new();
+	let slug = form.get("slug").unwrap_or(&empty);
+	let flag = form.get("flag").unwrap_or(&empty);
+	let outcome = submit_flag(&mut client, &session, &hub, &webhooks, slug, flag)?;
+	Ok(reply::with_status(reply::json(&Submission {
+		slug: slug.to_string(),
+		result: outcome.code(),
+		correct: outcome.correct(),
+		message: outcome.message(),
+	}), outcome.status()))
+}
+
+fn edit(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	macro_rules! profile_form {
+		($field:expr, $error:expr, $optional:expr) => {
+			match $field {
+				Some(value) if value.len() > 0 || $optional => value,
+				_ => return Ok(Response::builder()
+					.status(StatusCode::BAD_REQUEST)
+					.header("content-security-policy", "script-src 'none'")
+					.body(profile_body(client, session, Some($error))?)),
+			}
+		}
+	}
+	let passwordless: bool = result!(client.query("SELECT hash IS NULL AS passwordless FROM scrap.member
+		WHERE id=lookup_member($1)",
+		&[&session])).pop().map(|member| member.get("passwordless")).unwrap_or(false);
+	let discord = profile_form!(form.get("discord"), "Discord handle is required.", false);
+	let password = profile_form!(form.get("password"), "", true);
+	let current_password = profile_form!(form.get("current_password"), "Current password is required.", passwordless);
+	if !valid_discord(discord) {
+		return Ok(Response::builder()
+			.status(StatusCode::BAD_REQUEST)
+			.header("content-security-policy", "script-src 'none'")
+			.body(profile_body(client, session, Some("Invalid Discord handle."))?))
+	}
+	let id: i32 = match client.query("UPDATE scrap.member
+		SET discord=CASE WHEN discord_id IS NULL THEN $2 ELSE discord END,
+		hash=CASE WHEN ($3 != '') THEN crypt($3, gen_salt('bf')) ELSE hash END
+		WHERE id=lookup_member($1)
+		AND (hash IS NULL OR hash=crypt($4, hash))
+		RETURNING id",
+		&[&session, &discord, &password, &current_password]) {
+		Ok(members) if !members.is_empty() => members[0].get("id"),
+		Ok(_) => return Ok(Response::builder()
+			.status(StatusCode::UNAUTHORIZED)
+			.header("content-security-policy", "script-src 'none'")
+			.body(profile_body(client, session, Some("Incorrect password."))?)),
+		Err(ref e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => return Ok(Response::builder()
+			.status(StatusCode::BAD_REQUEST)
+			.header("content-security-policy", "script-src 'none'")
+			.body(profile_body(client, session, Some("Discord handle conflict."))?)),
+		Err(e) => return Err(custom(e)),
+	};
+	if password.is_empty() {
+		return Ok(Response::builder()
+			.header("location", "/profile")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string()));
+	}
+	// A new password signs out every other session, revokes API tokens and rotates this session.
+	let mut transaction = result!(client.transaction());
+	result!(transaction.execute("DELETE FROM scrap.session
+		WHERE member=$1 AND cookie!=$2",
+		&[&id, &session]));
+	result!(transaction.execute("DELETE FROM scrap.token
+		WHERE member=$1",
+		&[&id]));
+	let cookie: String = result!(transaction.query("UPDATE scrap.session
+		SET cookie=gen_random_uuid(), created=NOW(), seen=NOW()
+		WHERE cookie=$1
+		RETURNING cookie",
+		&[&session]))[0].get("cookie");
+	result!(transaction.commit());
+	Ok(Response::builder()
+		.header("location", "/profile")
+		.header("set-cookie", session_cookie(&mut client, &cookie)?)
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+fn revoke(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	if form.contains_key("all") {
+		let mut transaction = result!(client.transaction());
+		result!(transaction.execute("DELETE FROM scrap.token
+			WHERE member=lookup_member($1)",
+			&[&session]));
+		result!(transaction.execute("DELETE FROM scrap.session
+			WHERE member=lookup_member($1)",
+			&[&session]));
+		result!(transaction.commit());
+		return Ok(Response::builder()
+			.header("location", "/login")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string()));
+	}
+	if let Some(Ok(id)) = form.get("id").map(|id| id.parse::<i32>()) {
+		result!(client.execute("DELETE FROM scrap.session
+			WHERE member=lookup_member($1) AND id=$2",
+			&[&session, &id]));
+	}
+	Ok(Response::builder()
+		.header("location", "/profile")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+/// Changing division requires approval again where the new division needs it.
+fn change_division(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let empty = String::new();
+	let division = form.get("division").unwrap_or(&empty);
+	let email = form.get("email").unwrap_or(&empty);
+	let pending = match check_division(&mut client, division, email)? {
+		Ok(pending) => pending,
+		Err(error) => return Ok(Response::builder()
+			.status(StatusCode::BAD_REQUEST)
+			.header("content-security-policy", "script-src 'none'")
+			.body(profile_body(client, session, Some(error))?)),
+	};
+	result!(client.execute("UPDATE scrap.member
+		SET division=NULLIF($2, ''), email=COALESCE(NULLIF($3, ''), email), division_approved=$4
+		WHERE id=lookup_member($1)",
+		&[&session, division, email, &!pending]));
+	Ok(Response::builder()
+		.header("location", "/profile")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+fn manage_tokens(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let empty = String::new();
+	match form.get("action").unwrap_or(&empty).as_str() {
+		"create" => {
+			let name = form.get("name").unwrap_or(&empty).trim();
+			if name.is_empty() || name.len() > 64 {
+				return Ok(Response::builder()
+					.status(StatusCode::BAD_REQUEST)
+					.header("content-security-policy", "script-src 'none'")
+					.body(profile_body(client, session, Some("Token name is required."))?)
+					.into_response());
+			}
+			// Only a digest is stored, so the token is shown once.
+			let token: Option<String> = result!(client.query("WITH token AS (
+					SELECT $2 || encode(gen_random_bytes(20), 'hex') AS token
+				), inserted AS (
+					INSERT INTO scrap.token (member, name, hash)
+					SELECT lookup_member($1), $3, encode(digest(token, 'sha256'), 'hex') FROM token
+					WHERE lookup_member($1) IS NOT NULL
+				)
+				SELECT token FROM token
+				WHERE lookup_member($1) IS NOT NULL",
+				&[&session, &TOKEN_PREFIX, &name])).pop().map(|token| token.get("token"));
+			if let Some(token) = token {
+				// Keep the token out of browser and proxy caches.
+				return Ok(with_header(page("API Token", make_token(name, &token), client, session)?,
+					"cache-control", "no-store").into_response());
+			}
+		},
+		"revoke" => if let Some(Ok(id)) = form.get("id").map(|id| id.parse::<i32>()) {
+			result!(client.execute("DELETE FROM scrap.token
+				WHERE member=lookup_member($1) AND id=$2",
+				&[&session, &id]));
+		},
+		_ => (),
+	}
+	Ok(Response::builder()
+		.header("location", "/profile")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string())
+		.into_response())
+}
+
+fn manage_team(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let empty = String::new();
+	let action = form.get("action").unwrap_or(&empty);
+	let mut transaction = result!(client.transaction());
+	macro_rules! team_error {
+		($error:expr) => {{
+			drop(transaction);
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(profile_body(client, session, Some($error))?))
+		}}
+	}
+	let member = match result!(transaction.query("SELECT id, team, captain FROM scrap.member
+		WHERE id=lookup_member($1)
+		FOR UPDATE",
+		&[&session])).pop() {
+		Some(member) => member,
+		None => return Ok(Response::builder()
+			.header("location", "/login")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string())),
+	};
+	let id: i32 = member.get("id");
+	let team: Option<i32> = member.get("team");
+	let captain: bool = member.get("captain");
+	match (action.as_str(), team) {
+		("create", None) => {
+			let name = form.get("name").unwrap_or(&empty);
+			if name.is_empty() || name.len() > 64 || !name.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+				team_error!("Invalid team name length or characters.");
+			}
+			let team: i32 = match transaction.query("INSERT INTO scrap.team (name)
+				VALUES ($1)
+				RETURNING id",
+				&[&name]) {
+				Ok(teams) => teams[0].get("id"),
+				Err(ref e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+					team_error!("Team name conflict.");
+				},
+				Err(e) => return Err(custom(e)),
+			};
+			result!(transaction.execute("UPDATE scrap.member
+				SET team=$2, captain=true, joined=NOW()
+				WHERE id=$1",
+				&[&id, &team]));
+		},
+		("join", None) => {
+			let invite = form.get("invite").unwrap_or(&empty).trim().to_string();
+			let team = match result!(transaction.query("SELECT team.id,
+				ctf.team_size IS NOT NULL AND (SELECT COUNT(*) FROM scrap.member WHERE team=team.id) >= ctf.team_size AS full
+				FROM scrap.team team, scrap.ctf ctf
+				WHERE team.invite=$1 AND ctf.current
+				FOR UPDATE OF team",
+				&[&invite])).pop() {
+				Some(team) => team,
+				None => {
+					team_error!("Invalid invite code.");
+				},
+			};
+			if team.get("full") {
+				team_error!("Team is full.");
+			}
+			let team: i32 = team.get("id");
+			result!(transaction.execute("UPDATE scrap.member
+				SET team=$2, captain=false, joined=NOW()
+				WHERE id=$1",
+				&[&id, &team]));
+		},
+		("leave", Some(team)) => {
+			result!(transaction.execute("UPDATE scrap.member
+				SET team=NULL, captain=false, joined=NULL
+				WHERE id=$1",
+				&[&id]));
+			if captain {
+				result!(transaction.execute("UPDATE scrap.member
+					SET captain=true
+					WHERE id=(SELECT id FROM scrap.member WHERE team=$1 ORDER BY joined ASC NULLS FIRST, id ASC LIMIT 1)",
+					&[&team]));
+			}
+		},
+		("kick", Some(team)) if captain => {
+			if let Some(Ok(kicked)) = form.get("member").map(|member| member.parse::<i32>()) {
+				result!(transaction.execute("UPDATE scrap.member
+					SET team=NULL, captain=false, joined=NULL
+					WHERE id=$1 AND team=$2 AND id!=$3",
+					&[&kicked, &team, &id]));
+			}
+		},
+		("invite", Some(team)) if captain => {
+			result!(transaction.execute("UPDATE scrap.team
+				SET invite=encode(gen_random_bytes(8), 'hex')
+				WHERE id=$1",
+				&[&team]));
+		},
+		(_, Some(_)) => {
+			team_error!("You are already on a team.");
+		},
+		(_, None) => {
+			team_error!("You are not on a team.");
+		},
+	}
+	result!(transaction.commit());
+	Ok(Response::builder()
+		.header("location", "/profile")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+/// Accepts either a code from the member's authenticator or one of their unused recovery codes.
+fn check_code(client: &mut Client, member: i32, code: &str) -> Result<bool, Rejection> {
+	let code = code.trim().to_lowercase();
+	let row = match result!(client.query("SELECT totp, totp_step FROM scrap.member
+		WHERE id=$1",
+		&[&member])).pop() {
+		Some(row) => row,
+		None => return Ok(false),
+	};
+	let secret: Vec<u8> = match row.get("totp") {
+		Some(secret) => secret,
+		None => return Ok(false),
+	};
+	if let Some(step) = totp::verify(&secret, &code, row.get("totp_step")) {
+		// Each step is only accepted once so an observed code cannot be replayed, even by a concurrent request.
+		return Ok(result!(client.execute("UPDATE scrap.member
+			SET totp_step=$2
+			WHERE id=$1 AND (totp_step IS NULL OR totp_step < $2)",
+			&[&member, &step])) > 0);
+	}
+	Ok(result!(client.execute("DELETE FROM scrap.recovery
+		WHERE member=$1 AND hash=encode(digest($2, 'sha256'), 'hex')",
+		&[&member, &code])) > 0)
+}
+
+fn new_recovery_codes(transaction: &mut Transaction, member: i32) -> Result<Vec<String>, Rejection> {
+	result!(transaction.execute("DELETE FROM scrap.recovery
+		WHERE member=$1",
+		&[&member]));
+	Ok(result!(transaction.query("WITH codes AS (
+			SELECT encode(gen_random_bytes(5), 'hex') AS code FROM generate_series(1, 10)
+		), inserted AS (
+			INSERT INTO scrap.recovery (member, hash)
+			SELECT $1, encode(digest(code, 'sha256'), 'hex') FROM codes
+		)
+		SELECT code FROM codes",
+		&[&member])).iter().map(|row| row.get("code")).collect())
+}
+
+fn manage_totp(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	macro_rules! totp_error {
+		($error:expr) => {
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(profile_body(client, session, Some($error))?)
+				.into_response())
+		}
+	}
+	let empty = String::new();
+	let action = form.get("action").unwrap_or(&empty);
+	let code = form.get("code").unwrap_or(&empty);
+	let member = match result!(client.query("SELECT id, totp_pending FROM scrap.member
+		WHERE id=lookup_member($1)",
+		&[&session])).pop() {
+		Some(member) => member,
+		None => return Ok(Response::builder()
+			.header("location", "/login")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string())
+			.into_response()),
+	};
+	let id: i32 = member.get("id");
+	let codes = match action.as_str() {
+		"begin" => {
+			result!(client.execute("UPDATE scrap.member
+				SET totp_pending=gen_random_bytes(20)
+				WHERE id=$1 AND totp IS NULL",
+				&[&id]));
+			None
+		},
+		"confirm" => {
+			let pending: Option<Vec<u8>> = member.get("totp_pending");
+			let step = match pending.and_then(|secret| totp::verify(&secret, code.trim(), None)) {
+				Some(step) => step,
+				None => totp_error!("Incorrect authentication code."),
+			};
+			let mut transaction = result!(client.transaction());
+			result!(transaction.execute("UPDATE scrap.member
+				SET totp=totp_pending, totp_pending=NULL, totp_step=$2
+				WHERE id=$1",
+				&[&id, &step]));
+			let codes = new_recovery_codes(&mut transaction, id)?;
+			result!(transaction.commit());
+			Some(codes)
+		},
+		"recovery" | "disable" => {
+			if !check_code(&mut client, id, code)? {
+				totp_error!("Incorrect authentication code.");
+			}
+			let mut transaction = result!(client.transaction());
+			let codes = if action == "recovery" {
+				Some(new_recovery_codes(&mut transaction, id)?)
+			}
+			else {
+				result!(transaction.execute("UPDATE scrap.member
+					SET totp=NULL, totp_pending=NULL, totp_step=NULL
+					WHERE id=$1",
+					&[&id]));
+				result!(transaction.execute("DELETE FROM scrap.recovery
+					WHERE member=$1",
+					&[&id]));
+				None
+			};
+			result!(transaction.commit());
+			codes
+		},
+		_ => totp_error!("Unknown action."),
+	};
+	match codes {
+		// Keep the codes out of browser and proxy caches.
+		Some(codes) => Ok(with_header(page("Recovery Codes", make_recovery(&codes), client, session)?,
+			"cache-control", "no-store").into_response()),
+		None => Ok(Response::builder()
+			.header("location", "/profile")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string())
+			.into_response()),
+	}
+}
+
+fn register(mut client: Client, session: String, webhooks: Arc<Webhooks>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let divisions = get_divisions(&mut client)?;
+	let register_page = |error| make_register(&divisions, error);
+	macro_rules! register_form {
+		($field:expr, $error:expr) => {
+			form!($field, "Registration", $error, register_page, client, session)
+		}
+	}
+	macro_rules! register_error {
+		($error:expr) => {
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Registration", register_page(Some($error)), client, session)?))
+		}
+	}
+	let name = register_form!(form.get("name"), "Username is required.");
+	let discord = register_form!(form.get("discord"), "Discord handle is required.");
+	let password = register_form!(form.get("password"), "Password is required.");
+	let empty = String::new();
+	let division = form.get("division").unwrap_or(&empty);
+	let email = form.get("email").unwrap_or(&empty);
+	if name.len() > 64 || !name.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+		register_error!("Invalid username length or characters.");
+	}
+	if !valid_discord(discord) {
+		register_error!("Invalid Discord handle.");
+	}
+	let pending = match check_division(&mut client, division, email)? {
+		Ok(pending) => pending,
+		Err(error) => register_error!(error),
+	};
+	match client.execute("INSERT INTO scrap.member
+		(name, discord, hash, division, email, division_approved)
+		VALUES ($1, $2, crypt($3, gen_salt('bf')), NULLIF($4, ''), NULLIF($5, ''), $6)",
+		&[name, discord, password, division, email, &!pending]) {
+		Ok(_) => webhooks.send(Event::Registration { name: name.clone() }),
+		Err(ref e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => register_error!("Username or Discord handle conflict."),
+		Err(e) => return Err(custom(e)),
+	}
+	Ok(Response::builder()
+		.header("location", "/login")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+fn login(mut client: Client, session: String, agent: Option<String>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	macro_rules! login_form {
+		($field:expr, $error:expr) => {
+			form!($field, "Login", $error, make_login, client, session)
+		}
+	}
+	let name = login_form!(form.get("name"), "Username is required.");
+	let password = login_form!(form.get("password"), "Password is required.");
+	let id: i32 = match client.query("SELECT id FROM scrap.member
+		WHERE name=$1 AND hash=crypt($2, hash)",
+		&[name, password]) {
+		Ok(members) => match members.get(0) {
+			Some(member) => member.get("id"),
+			None => return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Login", make_login(Some("Invalid username or password.")), client, session)?)),
+		},
+		Err(e) => return Err(custom(e)),
+	};
+	sign_in(&mut client, id, &agent)
+}
+
+fn create_session(client: &mut Client, member: i32, agent: &Option<String>) -> Result<String, Rejection> {
+	Ok(result!(client.query("INSERT INTO scrap.session
+		(member, cookie, agent) VALUES ($1, gen_random_uuid(), $2)
+		RETURNING cookie",
+		&[&member, agent]))[0].get("cookie"))
+}
+
+/// `Set-Cookie` value for a session, kept by the browser for the CTF's `session_lifetime`.
+fn session_cookie(client: &mut Client, cookie: &str) -> Result<String, Rejection> {
+	let max_age: i64 = result!(client.query_one("SELECT EXTRACT(EPOCH FROM session_lifetime)::BIGINT AS max_age
+		FROM scrap.ctf WHERE current", &[])).get("max_age");
+	Ok(format!("session2={}; HttpOnly; SameSite=Lax; Max-Age={}; Path=/", cookie, max_age))
+}
+
+/// Starts a session, or a pending login when the member still has to enter a two-factor code.
+fn sign_in(client: &mut Client, member: i32, agent: &Option<String>) -> Result<Result<Response<String>, warp::http::Error>, Rejection> {
+	let pending: Option<String> = result!(client.query("INSERT INTO scrap.pending
+		(cookie, member, agent) SELECT gen_random_uuid(), id, $2 FROM scrap.member WHERE id=$1 AND totp IS NOT NULL
+		RETURNING cookie",
+		&[&member, agent])).pop().map(|pending| pending.get("cookie"));
+	if let Some(pending) = pending {
+		return Ok(Response::builder()
+			.header("location", "/login/totp")
+			.header("set-cookie", format!("pending={}; HttpOnly; SameSite=Lax; Max-Age=300; Path=/login/totp", pending))
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string()));
+	}
+	let cookie = create_session(client, member, agent)?;
+	Ok(Response::builder()
+		.header("location", "/challenges")
+		.header("set-cookie", session_cookie(client, &cookie)?)
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+fn login_totp(mut client: Client, session: String, pending: Option<String>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	macro_rules! totp_error {
+		($error:expr, $page:ident) => {
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Login", $page(Some($error)), client, session)?))
+		}
+	}
+	let pending = pending.unwrap_or_default();
+	// A pending login allows five attempts within five minutes.
+	let login = result!(client.query("UPDATE scrap.pending
+		SET attempts=attempts+1
+		WHERE cookie=$1 AND attempts < 5 AND created > NOW() - INTERVAL '5 minutes'
+		RETURNING member, agent",
+		&[&pending])).pop();
+	let (member, agent): (i32, Option<String>) = match login {
+		Some(login) => (login.get("member"), login.get("agent")),
+		None => totp_error!("Your login has expired. Log in again.", make_login),
+	};
+	// Members get ten codes every fifteen minutes however many pending logins they start.
+	// Each attempt counts as a failure until the code checks out, so parallel guesses cannot slip past the limit.
+	if result!(client.execute("UPDATE scrap.member
+		SET totp_failures=CASE WHEN totp_failed > NOW() - INTERVAL '15 minutes' THEN totp_failures+1 ELSE 1 END,
+		totp_failed=NOW()
+		WHERE id=$1 AND NOT (totp_failures >= 10 AND totp_failed > NOW() - INTERVAL '15 minutes')",
+		&[&member])) == 0 {
+		totp_error!("Too many incorrect authentication codes. Try again later.", make_login_totp);
+	}
+	let code = form.get("code").map(|code| code.as_str()).unwrap_or("");
+	if !check_code(&mut client, member, code)? {
+		totp_error!("Incorrect authentication code.", make_login_totp);
+	}
+	result!(client.execute("UPDATE scrap.member
+		SET totp_failures=0
+		WHERE id=$1",
+		&[&member]));
+	if result!(client.execute("DELETE FROM scrap.pending
+		WHERE cookie=$1",
+		&[&pending])) == 0 {
+		totp_error!("Your login has expired. Log in again.", make_login);
+	}
+	let cookie = create_session(&mut client, member, &agent)?;
+	Ok(Response::builder()
+		.header("location", "/challenges")
+		.header("set-cookie", session_cookie(&mut client, &cookie)?)
+		.header("set-cookie", "pending=; HttpOnly; SameSite=Lax; Max-Age=-1; Path=/login/totp")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+fn login_discord(mut client: Client) -> Result<impl Reply, Rejection> {
+	let oauth = match discord::oauth() {
+		Some(oauth) => oauth,
+		None => return Ok(Response::builder()
+			.header("location", "/login")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string())),
+	};
+	let state: String = result!(client.query("SELECT encode(gen_random_bytes(16), 'hex') AS state", &[]))[0].get("state");
+	Ok(Response::builder()
+		.header("location", oauth.authorize_url(&state))
+		.header("set-cookie", format!("oauth_state={}; HttpOnly; SameSite=Lax; Max-Age=600; Path=/login/discord", state))
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+fn discord_callback(mut client: Client, session: String, agent: Option<String>, state: Option<String>, webhooks: Arc<Webhooks>, query: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	macro_rules! login_error {
+		($error:expr) => {
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Login", make_login(Some($error)), client, session)?))
+		}
+	}
+	let user = match (discord::oauth(), query.get("code"), query.get("state"), state) {
+		(Some(oauth), Some(code), Some(returned), Some(state)) if *returned == state => match oauth.identify(code) {
+			Ok(user) => user,
+			Err(e) => {
+				eprintln!("Discord login failed: {}", e);
+				login_error!("Discord login failed.");
+			},
+		},
+		_ => login_error!("Discord login failed."),
+	};
+	let handle = user.handle();
+	let clear = "oauth_state=; HttpOnly; SameSite=Lax; Max-Age=-1; Path=/login/discord";
+	// A signed in member is linking Discord rather than logging in.
+	if let Some(member) = result!(client.query("SELECT lookup_member($1) AS id", &[&session]))[0].get::<_, Option<i32>>("id") {
+		return match client.execute("UPDATE scrap.member
+			SET discord_id=$2, discord=$3
+			WHERE id=$1",
+			&[&member, &user.id, &handle]) {
+			Ok(_) => Ok(Response::builder()
+				.header("location", "/profile")
+				.header("set-cookie", clear)
+				.status(StatusCode::SEE_OTHER)
+				.body("".to_string())),
+			Err(ref e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.header("set-cookie", clear)
+				.body(profile_body(client, session, Some("That Discord account belongs to another user."))?)),
+			Err(e) => Err(custom(e)),
+		};
+	}
+	let existing = result!(client.query("SELECT id FROM scrap.member
+		WHERE discord_id=$1",
+		&[&user.id])).pop();
+	let id: i32 = match existing {
+		Some(member) => member.get("id"),
+		None => match client.query("INSERT INTO scrap.member
+			(name, discord, discord_id) VALUES ($1, $2, $3)
+			RETURNING id",
+			&[&user.username, &handle, &user.id]) {
+			Ok(members) => {
+				webhooks.send(Event::Registration { name: user.username.clone() });
+				members[0].get("id")
+			},
+			Err(ref e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+				login_error!("That username or Discord handle is taken. Log in with your password and verify Discord from your profile.");
+			},
+			Err(e) => return Err(custom(e)),
+		},
+	};
+	let mut response = sign_in(&mut client, id, &agent)?;
+	if let Ok(response) = &mut response {
+		response.headers_mut().append("set-cookie", HeaderValue::from_static(clear));
+	}
+	Ok(response)
+}
+
+fn logout(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+	match client.execute("DELETE FROM scrap.session
+		WHERE cookie=$1",
+		&[&session]) {
+		Ok(_n) => Ok(Response::builder()
+			.header("location", "/")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string())),
+		Err(e) => return Err(custom(e)),
+	}
+}
+
+fn redeem(mut client: Client, session: String, webhooks: Arc<Webhooks>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let regular = match form.get("type").map(|kind| kind.as_str()) {
+		Some("regular") => true,
+		Some("premium") => false,
+		_ => return Ok(Response::builder()
+			.status(StatusCode::BAD_REQUEST)
+			.header("content-security-policy", "script-src 'none'")
+			.body("Invalid request".to_string())
+			.into_response()),
+	};
+	// The team row stays locked until commit so concurrent redemptions cannot spend the same ticket.
+	let mut transaction = result!(client.transaction());
+	let team = result!(transaction.query("SELECT team.id, team.name, team.score - team.redeemed_score AS points,
+		team.premium_tickets, team.banned, member.name AS member, member.discord
+		FROM scrap.team team
+		JOIN scrap.member member ON member.id=lookup_member($1)
+		WHERE team.id=lookup($1)
+		FOR UPDATE OF team",
+		&[&session])).pop();
+	let team = match team {
+		Some(team) if !team.get::<_, bool>("banned") => team,
+		_ => {
+			drop(transaction);
+			return Ok(Response::builder()
+				.header("location", "/rewards")
+				.status(StatusCode::SEE_OTHER)
+				.body("".to_string())
+				.into_response());
+		},
+	};
+	let id: i32 = team.get("id");
+	let ticket_points: i32 = result!(transaction.query_one("SELECT ticket_points FROM scrap.ctf WHERE current", &[])).get("ticket_points");
+	let catalog: Vec<CatalogPrize> = result!(transaction.query("SELECT name, weight, one_time FROM scrap.catalog
+		ORDER BY position ASC",
+		&[])).iter().map(|prize| CatalogPrize {
+			name: prize.get("name"),
+			weight: prize.get("weight"),
+			one_time: prize.get("one_time"),
+		}).collect();
+	let owned: Vec<String> = result!(transaction.query("SELECT prize FROM scrap.prize
+		WHERE team=$1",
+		&[&id])).iter().map(|prize| prize.get("prize")).collect();
+	let roll: f64 = result!(transaction.query_one("SELECT random()", &[])).get(0);
+	let prize = if regular {
+		match draw_prize(&catalog, &owned, roll) {
+			Some(prize) if team.get::<_, i32>("points") >= ticket_points => {
+				let name = prize.name.clone();
+				result!(transaction.execute("UPDATE scrap.team SET redeemed_score=redeemed_score+$2
+					WHERE id=$1",
+					&[&id, &ticket_points]));
+				if prize.one_time {
+					result!(transaction.execute("INSERT INTO scrap.prize (team, prize)
+						VALUES ($1, $2)
+						ON CONFLICT DO NOTHING",
+						&[&id, &name]));
+				}
+				result!(transaction.execute("INSERT INTO scrap.draw (team, member, prize)
+					VALUES ($1, lookup_member($2), $3)",
+					&[&id, &session, &name]));
+				Some(Some(name))
+			},
+			_ => None,
+		}
+	} else if team.get::<_, i32>("premium_tickets") < 1 {
+		None
+	} else {
+		result!(transaction.execute("UPDATE scrap.team SET premium_tickets=premium_tickets-1
+			WHERE id=$1",
+			&[&id]));
+		result!(transaction.execute("INSERT INTO scrap.raffle (team, member)
+			VALUES ($1, lookup_member($2))",
+			&[&id, &session]));
+		Some(None)
+	};
+	result!(transaction.commit());
+	if let Some(Some(prize)) = &prize {
+		webhooks.send(Event::Prize {
+			team: team.get("name"),
+			member: team.get("member"),
+			discord: team.get("discord"),
+			prize: prize.clone(),
+		});
+	}
+	Ok(page("Rewards", make_redeem(prize.as_ref().map(|prize| prize.as_deref())), client, session)?.into_response())
+}
+
+fn gib_tickets(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	macro_rules! admin_form {
+		($field:expr, $error:expr) => {
+			form!($field, "Admin", $error, make_admin, client, session)
+		}
+	}
+	macro_rules! admin_error {
+		($error:expr) => {
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Admin", make_admin(Some($error)), client, session)?))
+		}
+	}
+	if !is_admin(&mut client, &session)? {
+		return Ok(Response::builder()
+			.header("location", "/admin")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string()));
+	}
+	let name = admin_form!(form.get("name"), "Team name is required.");
+	// Writeups earn the premium tickets granted for the challenge's value in ctf.toml.
+	let tickets = match form.get("challenge").filter(|slug| !slug.is_empty()) {
+		Some(slug) => {
+			let challenge = result!(client.query("SELECT
+				(SELECT tickets FROM scrap.grant
+				WHERE max_value IS NULL OR max_value >= challenge.value
+				ORDER BY position ASC
+				LIMIT 1) AS tickets
+				FROM scrap.challenge challenge
+				WHERE slug=$1 AND ctf=current_ctf()",
+				&[&slug])).pop();
+			match challenge.map(|challenge| challenge.get::<_, Option<i32>>("tickets")) {
+				Some(Some(tickets)) => tickets,
+				Some(None) => admin_error!("No premium tickets are granted for that challenge's value."),
+				None => admin_error!("No such challenge."),
+			}
+		},
+		None => match admin_form!(form.get("tickets"), "Number of tickets or a writeup challenge is required.").trim().parse::<i32>() {
+			Ok(tickets) => tickets,
+			Err(_) => admin_error!("Number of tickets must be a whole number."),
+		},
+	};
+	if result!(client.execute("UPDATE scrap.team SET premium_tickets=GREATEST(premium_tickets+$2, 0)
+		WHERE name=$1",
+		&[&name, &tickets])) == 0 {
+		admin_error!("No such team.");
+	}
+	Ok(Response::builder()
+		.header("location", "/admin")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
 }
 
-fn error(err: Rejection) -> Result<impl Reply, Rejection> {
-	match err.status() {
-		StatusCode::METHOD_NOT_ALLOWED => {
-			Ok(Response::builder()
-				.status(StatusCode::NOT_FOUND)
-				.body("404 Page Not Found"))
-		},
-		_ => {
-			Ok(Response::builder()
-				.status(StatusCode::INTERNAL_SERVER_ERROR)
-				.body("500 Internal Server Error"))
+fn approve_division(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	if is_admin(&mut client, &session)? {
+		if let Some(Ok(member)) = form.get("member").map(|member| member.parse::<i32>()) {
+			match form.get("action").map(|action| action.as_str()) {
+				Some("approve") => {
+					result!(client.execute("UPDATE scrap.member SET division_approved=true
+						WHERE id=$1",
+						&[&member]));
+				},
+				Some("reject") => {
+					result!(client.execute("UPDATE scrap.member SET division=NULL, division_approved=false
+						WHERE id=$1",
+						&[&member]));
+				},
+				_ => (),
+			}
 		}
 	}
+	Ok(Response::builder()
+		.header("location", "/admin")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
 }
 
-fn submit(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
-	let now = Utc::now();
-	let ctf = &result!(client.query("SELECT start, stop FROM scrap.ctf", &[]))[0];
-	if ctf.try_get("start").map(|start: DateTime<Utc>| now < start).unwrap_or(false) || 
-		ctf.try_get("stop").map(|stop: DateTime<Utc>| now > stop).unwrap_or(false) {
+fn set_team_state(mut client: Client, session: String, hub: Arc<Hub>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	if !is_admin(&mut client, &session)? {
 		return Ok(Response::builder()
-			.header("location", "/challenges")
+			.header("location", "/admin")
 			.status(StatusCode::SEE_OTHER)
 			.body("".to_string()));
 	}
+	macro_rules! admin_form {
+		($field:expr, $error:expr) => {
+			form!($field, "Admin", $error, make_admin, client, session)
+		}
+	}
+	macro_rules! admin_error {
+		($error:expr) => {
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Admin", make_admin(Some($error)), client, session)?))
+		}
+	}
+	let name = admin_form!(form.get("name"), "Team name is required.");
+	let state = admin_form!(form.get("state"), "Team state is required.");
 	let empty = String::new();
-	let slug = form.get("slug").unwrap_or(&empty);
-	let flag = form.get("flag").unwrap_or(&empty);
+	let reason = form.get("reason").unwrap_or(&empty);
+	let (hidden, banned) = match state.as_str() {
+		"visible" => (false, false),
+		"hidden" => (true, false),
+		"banned" => (false, true),
+		_ => admin_error!("Unknown team state."),
+	};
 	let mut transaction = result!(client.transaction());
-	let rows = result!(transaction.execute("UPDATE scrap.team team
-		SET solves=update(team.solves, challenge.id), submit=NOW()
-		FROM scrap.challenge challenge
-		WHERE team.id=lookup($1)
-		AND slug=$2 AND flag=$3
-		AND NOT solved(team.solves, challenge.id)",
-		&[&session, &slug, &flag])) as i32;
-	if rows > 0 {
-		result!(transaction.execute("UPDATE scrap.challenge
-			SET solves=solves+$2
-			WHERE slug=$1",
-			&[&slug, &rows]));
-		result!(transaction.execute("UPDATE scrap.team team
-			SET score=COALESCE((SELECT SUM(challenge.value)
-			FROM scrap.challenge challenge
-			WHERE solved(team.solves, challenge.id)), 0)",
-			&[]));
-		result!(transaction.commit());
+	let team: i32 = match result!(transaction.query("UPDATE scrap.team
+		SET hidden=$2, banned=$3
+		WHERE name=$1
+		RETURNING id",
+		&[&name, &hidden, &banned])).pop() {
+		Some(team) => team.get("id"),
+		None => {
+			drop(transaction);
+			admin_error!("No such team.");
+		},
+	};
+	result!(transaction.execute("INSERT INTO scrap.audit (admin, team, action, reason)
+		VALUES (lookup_member($1), $2, $3, NULLIF($4, ''))",
+		&[&session, &team, &state, &reason]));
+	count_solves(&mut transaction)?;
+	result!(transaction.commit());
+	publish_scoreboard(&mut client, &hub);
+	Ok(Response::builder()
+		.header("location", "/admin")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+fn adjust_score(mut client: Client, session: String, hub: Arc<Hub>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	if !is_admin(&mut client, &session)? {
 		return Ok(Response::builder()
-			.header("location", "/challenges")
+			.header("location", "/admin")
 			.status(StatusCode::SEE_OTHER)
 			.body("".to_string()));
-	} else { 
+	}
+	macro_rules! admin_form {
+		($field:expr, $error:expr) => {
+			form!($field, "Admin", $error, make_admin, client, session)
+		}
+	}
+	macro_rules! admin_error {
+		($error:expr) => {
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Admin", make_admin(Some($error)), client, session)?))
+		}
+	}
+	if form.get("action").map(|action| action.as_str()) == Some("delete") {
+		let id = match form.get("id").map(|id| id.parse::<i32>()) {
+			Some(Ok(id)) => id,
+			_ => admin_error!("Unknown adjustment."),
+		};
+		let mut transaction = result!(client.transaction());
+		result!(transaction.execute("WITH adjustment AS (
+				DELETE FROM scrap.adjustment WHERE id=$2 RETURNING team, delta, reason
+			)
+			INSERT INTO scrap.audit (admin, team, action, reason)
+			SELECT lookup_member($1), team, format('removed adjustment of %s', delta), reason FROM adjustment",
+			&[&session, &id]));
+		update_scores(&mut transaction)?;
+		result!(transaction.commit());
+	}
+	else {
+		let name = admin_form!(form.get("name"), "Team name is required.");
+		let delta = admin_form!(form.get("delta"), "Points are required.");
+		let reason = admin_form!(form.get("reason"), "A reason is required.");
+		let delta = match delta.trim().trim_start_matches('+').parse::<i32>() {
+			Ok(delta) => delta,
+			Err(_) => admin_error!("Points must be a whole number."),
+		};
+		let mut transaction = result!(client.transaction());
+		if result!(transaction.execute("INSERT INTO scrap.adjustment (team, delta, reason, admin)
+			SELECT id, $2, $3, lookup_member($4) FROM scrap.team
+			WHERE name=$1",
+			&[&name, &delta, &reason, &session])) == 0 {
+			drop(transaction);
+			admin_error!("No such team.");
+		}
+		update_scores(&mut transaction)?;
+		result!(transaction.commit());
+	}
+	publish_scoreboard(&mut client, &hub);
+	Ok(Response::builder()
+		.header("location", "/admin")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+#[derive(Serialize)]
+struct Announcement {
+	message: String,
+	challenge: Option<String>,
+	time: String,
+}
+
+fn manage_announcements(mut client: Client, session: String, hub: Arc<Hub>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	if !is_admin(&mut client, &session)? {
 		return Ok(Response::builder()
-			.header("location", "/challenges")
-			.header("set-cookie", format!("invalid={}; HttpOnly; SameSite=Lax", slug))
+			.header("location", "/admin")
 			.status(StatusCode::SEE_OTHER)
 			.body("".to_string()));
 	}
-}
-
-fn edit(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
-	let team = match client.query("SELECT name, discord FROM scrap.team
-		WHERE id=lookup($1)",
-		&[&session]) {
-		Ok(mut teams) => teams.pop(),
-		Err(e) => return Err(custom(e)),
-	};
-	macro_rules! profile_form {
-		($field:expr, $error:expr, $optional:expr) => {
-			match $field {
-				Some(value) if value.len() > 0 || $optional => value,
-				_ => return Ok(Response::builder()
-					.status(StatusCode::BAD_REQUEST)
-					.header("content-security-policy", "script-src 'none'")
-					.body(make_body("Profile", make_profile(team, Some($error)), client, session)?)),
-			}
+	macro_rules! admin_form {
+		($field:expr, $error:expr) => {
+			form!($field, "Admin", $error, make_admin, client, session)
 		}
 	}
-	let discord = profile_form!(form.get("discord"), "Discord handle is required.", false);
-	let password = profile_form!(form.get("password"), "", true);
-	let current_password = profile_form!(form.get("current_password"), "Current password is required.", false);
-	let re = Regex::new(r"^.{2,32}?#\d{4}$").unwrap();
-	if !re.is_match(discord) {
-		return Ok(Response::builder()
-			.status(StatusCode::BAD_REQUEST)
-			.header("content-security-policy", "script-src 'none'")
-			.body(make_body("Profile", make_profile(team, Some("Invalid Discord handle.")), client, session)?))
+	if form.get("action").map(|action| action.as_str()) == Some("delete") {
+		if let Some(Ok(id)) = form.get("id").map(|id| id.parse::<i32>()) {
+			result!(client.execute("DELETE FROM scrap.announcement
+				WHERE id=$1",
+				&[&id]));
+		}
 	}
-	match client.execute("UPDATE scrap.team
-		SET discord=$2, hash=CASE WHEN ($3 != '') THEN crypt($3, gen_salt('bf')) ELSE hash END
-		WHERE id=lookup($1)
-		AND hash=crypt($4, hash)",
-		&[&session, &discord, &password, &current_password]) {
-		Ok(n) if n > 0 => (),
-		Ok(_) => return Ok(Response::builder()
-			.status(StatusCode::UNAUTHORIZED)
-			.header("content-security-policy", "script-src 'none'")
-			.body(make_body("Profile", make_profile(team, Some("Incorrect password.")), client, session)?)),
-		Err(ref e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => return Ok(Response::builder()
-			.status(StatusCode::BAD_REQUEST)
-			.header("content-security-policy", "script-src 'none'")
-			.body(make_body("Profile", make_profile(team, Some("Discord handle conflict.")), client, session)?)),
-		Err(e) => return Err(custom(e)),
+	else {
+		let message = admin_form!(form.get("message"), "Announcement is required.");
+		let challenge = form.get("challenge").map(|challenge| challenge.trim()).filter(|challenge| !challenge.is_empty());
+		let announcement = match result!(client.query("INSERT INTO scrap.announcement (message, challenge, admin)
+			SELECT $1, (SELECT id FROM scrap.challenge WHERE slug=$2 AND ctf=current_ctf()), lookup_member($3)
+			WHERE $2::TEXT IS NULL OR EXISTS (SELECT FROM scrap.challenge WHERE slug=$2 AND ctf=current_ctf())
+			RETURNING time",
+			&[&message, &challenge, &session])).pop() {
+			Some(announcement) => announcement,
+			None => return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Admin", make_admin(Some("No such challenge.")), client, session)?)),
+		};
+		let time: DateTime<Utc> = announcement.get("time");
+		hub.publish("announcement", &Announcement {
+			message: message.clone(),
+			challenge: challenge.map(|challenge| challenge.to_string()),
+			time: time.format("%Y-%m-%d %H:%M UTC").to_string(),
+		});
 	}
 	Ok(Response::builder()
-		.header("location", "/profile")
+		.header("location", "/admin")
 		.status(StatusCode::SEE_OTHER)
 		.body("".to_string()))
 }
 
-fn register(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
-	macro_rules! register_form {
+fn set_inventory(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	if !is_admin(&mut client, &session)? {
+		return Ok(Response::builder()
+			.header("location", "/admin")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string()));
+	}
+	macro_rules! admin_form {
 		($field:expr, $error:expr) => {
-			form!($field, "Registration", $error, make_register, client, session)
+			form!($field, "Admin", $error, make_admin, client, session)
 		}
 	}
-	let name = register_form!(form.get("name"), "Team name is required.");
-	let discord = register_form!(form.get("discord"), "Discord handle is required.");
-	let password = register_form!(form.get("password"), "Password is required.");
-	if name.len() > 64 || !name.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
-		return Ok(Response::builder()
-			.status(StatusCode::BAD_REQUEST)
-			.header("content-security-policy", "script-src 'none'")
-			.body(make_body("Registration", make_register(Some("Invalid team name length or characters.")), client, session)?))
+	macro_rules! admin_error {
+		($error:expr) => {
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Admin", make_admin(Some($error)), client, session)?))
+		}
 	}
-	let re = Regex::new(r"^.{2,32}?#\d{4}$").unwrap();
-	if !re.is_match(discord) {
+	let name = admin_form!(form.get("name"), "Prize name is required.");
+	let quantity = admin_form!(form.get("quantity"), "Quantity is required.");
+	let quantity = match quantity.trim().parse::<i32>() {
+		Ok(quantity) if quantity >= 0 => quantity,
+		_ => admin_error!("Quantity must be a whole number."),
+	};
+	result!(client.execute("INSERT INTO scrap.inventory (name, quantity)
+		VALUES ($1, $2)
+		ON CONFLICT (name) DO UPDATE SET quantity=$2",
+		&[&name.trim(), &quantity]));
+	Ok(Response::builder()
+		.header("location", "/admin")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
+}
+
+/// Awards each unclaimed raffle prize to an entry. Eligible entries are ranked by the SHA-256 of
+/// `<seed>:<entry id>` and prize units are handed out in inventory order, so anyone with the seed,
+/// recorded entries and prize units can reproduce the winners.
+fn draw_raffle(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+	if !is_admin(&mut client, &session)? {
 		return Ok(Response::builder()
-			.status(StatusCode::BAD_REQUEST)
-			.header("content-security-policy", "script-src 'none'")
-			.body(make_body("Registration", make_register(Some("Invalid Discord handle.")), client, session)?))
-	}
-	match client.execute("INSERT INTO scrap.team
-		(name, discord, hash) VALUES ($1, $2, crypt($3, gen_salt('bf')))",
-		&[name, discord, password]) {
-		Ok(_) => (),
-		Err(ref e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => return Ok(Response::builder()
-			.status(StatusCode::BAD_REQUEST)
-			.header("content-security-policy", "script-src 'none'")
-			.body(make_body("Registration", make_register(Some("Team name or Discord handle conflict.")), client, session)?)),
-		Err(e) => return Err(custom(e)),
+			.header("location", "/admin")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string()));
 	}
+	let mut transaction = result!(client.transaction());
+	result!(transaction.execute("LOCK TABLE scrap.winner IN EXCLUSIVE MODE", &[]));
+	// The seed is always random, and the remaining prize units are kept with the drawing.
+	let drawing: i32 = result!(transaction.query_one("INSERT INTO scrap.drawing (seed, entries, prizes, admin)
+		SELECT encode(gen_random_bytes(16), 'hex'),
+		ARRAY(SELECT raffle.id FROM scrap.raffle raffle
+			JOIN scrap.team team ON team.id=raffle.team
+			WHERE NOT team.hidden AND NOT team.banned
+			AND NOT EXISTS (SELECT FROM scrap.winner winner WHERE winner.entry=raffle.id)
+			ORDER BY raffle.id ASC),
+		ARRAY(SELECT inventory.name FROM scrap.inventory inventory,
+			generate_series(1, inventory.quantity
+				- (SELECT COUNT(*) FROM scrap.winner winner WHERE winner.inventory=inventory.id)::INTEGER) unit
+			ORDER BY inventory.id, unit),
+		lookup_member($1)
+		RETURNING id",
+		&[&session])).get("id");
+	let winners = result!(transaction.execute("INSERT INTO scrap.winner (drawing, entry, inventory)
+		SELECT $1, result.entry, inventory.id
+		FROM drawing_results($1) result
+		JOIN scrap.inventory inventory ON inventory.name=result.prize",
+		&[&drawing]));
+	result!(transaction.execute("INSERT INTO scrap.audit (admin, action)
+		SELECT admin, format('drew %s raffle winners with seed %s', $2::BIGINT, seed) FROM scrap.drawing
+		WHERE id=$1",
+		&[&drawing, &(winners as i64)]));
+	result!(transaction.commit());
 	Ok(Response::builder()
-		.header("location", "/login")
+		.header("location", "/admin")
 		.status(StatusCode::SEE_OTHER)
 		.body("".to_string()))
 }
 
-fn login(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
-	macro_rules! login_form {
-		($field:expr, $error:expr) => {
-			form!($field, "Login", $error, make_login, client, session)
-		}
+/// Recomputes a recorded drawing from its seed, entries and prize units, and checks the result
+/// against the winners it awarded.
+fn verify_raffle(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	if !is_admin(&mut client, &session)? {
+		return Ok(Response::builder()
+			.header("location", "/admin")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string())
+			.into_response());
 	}
-	let name = login_form!(form.get("name"), "Team name is required.");
-	let password = login_form!(form.get("password"), "Password is required.");
-	let id: i32 = match client.query("SELECT id FROM scrap.team
-		WHERE name=$1 AND hash=crypt($2, hash)",
-		&[name, password]) {
-		Ok(teams) => match teams.get(0) {
-			Some(team) => team.get("id"),
-			None => return Ok(Response::builder()
+	macro_rules! admin_error {
+		($error:expr) => {
+			return Ok(Response::builder()
 				.status(StatusCode::BAD_REQUEST)
 				.header("content-security-policy", "script-src 'none'")
-				.body(make_body("Login", make_login(Some("Invalid team name or password.")), client, session)?)),
-		},
-		Err(e) => return Err(custom(e)),
+				.body(make_body("Admin", make_admin(Some($error)), client, session)?)
+				.into_response())
+		}
+	}
+	let id = match form.get("id").map(|id| id.parse::<i32>()) {
+		Some(Ok(id)) => id,
+		_ => admin_error!("Drawing is required."),
 	};
-	let cookie: String = match client.query("INSERT INTO scrap.session
-		(team, cookie) VALUES ($1, gen_random_uuid())
-		RETURNING cookie",
-		&[&id]) {
-		Ok(sessions) => sessions[0].get("cookie"),
-		Err(e) => return Err(custom(e)),
+	let drawing = match result!(client.query("WITH recorded AS (
+			SELECT winner.entry, inventory.name AS prize
+			FROM scrap.winner winner
+			JOIN scrap.inventory inventory ON inventory.id=winner.inventory
+			WHERE winner.drawing=$1
+		)
+		SELECT drawing.seed, drawing.prizes IS NOT NULL AS snapshot,
+		(SELECT COUNT(*) FROM recorded) AS winners,
+		NOT EXISTS (SELECT * FROM drawing_results($1) EXCEPT SELECT * FROM recorded)
+		AND NOT EXISTS (SELECT * FROM recorded EXCEPT SELECT * FROM drawing_results($1)) AS verified
+		FROM scrap.drawing drawing
+		WHERE drawing.id=$1",
+		&[&id])).pop() {
+		Some(drawing) => drawing,
+		None => admin_error!("No such drawing."),
 	};
+	if !drawing.get::<_, bool>("snapshot") {
+		admin_error!("This drawing was recorded without its prize units, so it cannot be verified.");
+	}
+	if !drawing.get::<_, bool>("verified") {
+		admin_error!("This drawing does not match its recorded winners.");
+	}
+	let seed: String = drawing.get("seed");
+	let winners: i64 = drawing.get("winners");
+	Ok(page("Admin", html! {
+		(make_admin(None))
+		section {
+			p { "Drawing with seed " code { (seed) } " verified: its entries and prize units reproduce all " (winners) " winners." }
+		}
+	}, client, session)?.into_response())
+}
+
+fn fulfill_prize(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	if is_admin(&mut client, &session)? {
+		if let Some(Ok(id)) = form.get("id").map(|id| id.parse::<i32>()) {
+			match form.get("kind").map(|kind| kind.as_str()) {
+				Some("raffle") => {
+					result!(client.execute("UPDATE scrap.winner SET fulfilled=NOW()
+						WHERE id=$1 AND fulfilled IS NULL",
+						&[&id]));
+				},
+				Some("draw") => {
+					result!(client.execute("UPDATE scrap.draw SET fulfilled=NOW()
+						WHERE id=$1 AND fulfilled IS NULL",
+						&[&id]));
+				},
+				_ => (),
+			}
+		}
+	}
 	Ok(Response::builder()
-		.header("location", "/challenges")
-		.header("set-cookie", format!("session2={}; HttpOnly; SameSite=Lax; Max-Age=86400", cookie))
+		.header("location", "/admin")
 		.status(StatusCode::SEE_OTHER)
 		.body("".to_string()))
 }
 
-fn logout(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
-	match client.execute("DELETE FROM scrap.session
-		WHERE cookie=$1",
-		&[&session]) {
-		Ok(_n) => Ok(Response::builder()
-			.header("location", "/")
+fn csv_field(field: &str) -> String {
+	if field.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_string()
+	}
+}
+
+/// Every raffle winner and gacha prize, for handing prizes out.
+fn get_winners_csv(mut client: Client, session: String) -> Result<impl Reply, Rejection> {
+	if !is_admin(&mut client, &session)? {
+		return Ok(Response::builder()
+			.header("location", "/admin")
 			.status(StatusCode::SEE_OTHER)
-			.body("".to_string())),
-		Err(e) => return Err(custom(e)),
+			.body("".to_string()));
+	}
+	let prizes = result!(client.query("SELECT prize.source, prize.prize, team.name AS team, member.name AS member, member.discord,
+		prize.time, prize.fulfilled, prize.seed
+		FROM (
+			SELECT 'raffle' AS source, inventory.name AS prize, raffle.team, raffle.member, drawing.time, winner.fulfilled, drawing.seed
+			FROM scrap.winner winner
+			JOIN scrap.raffle raffle ON raffle.id=winner.entry
+			JOIN scrap.inventory inventory ON inventory.id=winner.inventory
+			JOIN scrap.drawing drawing ON drawing.id=winner.drawing
+			UNION ALL
+			SELECT 'gacha', prize, team, member, time, fulfilled, NULL
+			FROM scrap.draw
+		) prize
+		JOIN scrap.team team ON team.id=prize.team
+		LEFT JOIN scrap.member member ON member.id=prize.member
+		ORDER BY prize.time ASC",
+		&[]));
+	let mut csv = "source,prize,team,member,discord,time,fulfilled,seed\r\n".to_string();
+	for prize in &prizes {
+		let time: DateTime<Utc> = prize.get("time");
+		let fulfilled: Option<DateTime<Utc>> = prize.get("fulfilled");
+		let fields = [
+			prize.get("source"),
+			prize.get("prize"),
+			prize.get("team"),
+			prize.get::<_, Option<String>>("member").unwrap_or_default(),
+			prize.get::<_, Option<String>>("discord").unwrap_or_default(),
+			time.to_rfc3339(),
+			fulfilled.map(|fulfilled| fulfilled.to_rfc3339()).unwrap_or_default(),
+			prize.get::<_, Option<String>>("seed").unwrap_or_default(),
+		];
+		csv.push_str(&fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","));
+		csv.push_str("\r\n");
 	}
+	Ok(Response::builder()
+		.header("content-type", "text/csv; charset=utf-8")
+		.header("content-disposition", "attachment; filename=\"winners.csv\"")
+		.body(csv))
 }
 
-fn gib_tickets(mut client: Client, session: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
-	macro_rules! admin_form {
-		($field:expr, $error:expr) => {
-			form!($field, "Admin", $error, make_admin, client, session)
-		}
+fn create_reset(mut client: Client, session: String, base_url: String, notifier: Option<Arc<dyn Notifier>>, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	if !is_admin(&mut client, &session)? {
+		return Ok(Response::builder()
+			.header("location", "/admin")
+			.status(StatusCode::SEE_OTHER)
+			.body("".to_string())
+			.into_response());
 	}
-	let name = admin_form!(form.get("name"), "Team name is required.");
-	let tickets = admin_form!(form.get("tickets"), "Number of Tickets are required.");
-	let num_tickets: i32 = tickets.parse::<i32>().unwrap();
-	match client.execute("UPDATE scrap.team SET premium_tickets=premium_tickets+$2
+	let name = match form.get("name") {
+		Some(name) if !name.is_empty() => name,
+		_ => return Ok(Response::builder()
+			.status(StatusCode::BAD_REQUEST)
+			.header("content-security-policy", "script-src 'none'")
+			.body(make_body("Admin", make_admin(Some("Username is required.")), client, session)?)
+			.into_response()),
+	};
+	let member = match result!(client.query("SELECT id, discord FROM scrap.member
 		WHERE name=$1",
-		&[&name, &num_tickets]) {
-			Ok(_n) => Ok(Response::builder()
-				.header("location", "/admin")
-				.status(StatusCode::SEE_OTHER)
-				.body("gibben".to_string())),
-			Err(e) => return Err(custom(e)),
+		&[&name])).pop() {
+		Some(member) => member,
+		None => return Ok(Response::builder()
+			.status(StatusCode::BAD_REQUEST)
+			.header("content-security-policy", "script-src 'none'")
+			.body(make_body("Admin", make_admin(Some("No such user.")), client, session)?)
+			.into_response()),
+	};
+	let id: i32 = member.get("id");
+	let discord: String = member.get("discord");
+	// Only a digest of the token is stored, and a new link replaces any earlier one.
+	let token: String = result!(client.query("SELECT encode(gen_random_bytes(24), 'hex') AS token", &[]))[0].get("token");
+	let mut transaction = result!(client.transaction());
+	result!(transaction.execute("DELETE FROM scrap.reset
+		WHERE member=$1",
+		&[&id]));
+	result!(transaction.execute("INSERT INTO scrap.reset (hash, member, expires)
+		VALUES (encode(digest($1, 'sha256'), 'hex'), $2, NOW() + INTERVAL '1 day')",
+		&[&token, &id]));
+	result!(transaction.commit());
+	let link = format!("{}/reset/{}", base_url, token);
+	let delivery = notifier.map(|notifier| notifier.notify(name, &discord, &format!("Reset your password: {}", link)));
+	// Keep the link out of browser and proxy caches.
+	Ok(with_header(page("Admin", html! {
+		(make_admin(None))
+		section {
+			p { "Reset link for " (name) " (expires in 1 day):" }
+			p { code { (link) } }
+			@match delivery {
+				Some(Ok(())) => p { "The link was sent to " (discord) "." },
+				Some(Err(e)) => p class="error" { "Failed to send the link: " (e.to_string()) },
+				None => {},
+			}
 		}
+	}, client, session)?, "cache-control", "no-store").into_response())
+}
+
+fn reset(mut client: Client, session: String, token: String, form: HashMap<String, String>) -> Result<impl Reply, Rejection> {
+	let password = match form.get("password") {
+		Some(value) if !value.is_empty() => value,
+		_ => return Ok(Response::builder()
+			.status(StatusCode::BAD_REQUEST)
+			.header("content-security-policy", "script-src 'none'")
+			.body(make_body("Reset Password", make_reset(true, Some("Password is required.")), client, session)?)),
+	};
+	let mut transaction = result!(client.transaction());
+	let member: i32 = match result!(transaction.query("DELETE FROM scrap.reset
+		WHERE hash=encode(digest($1, 'sha256'), 'hex') AND expires > NOW()
+		RETURNING member",
+		&[&token])).pop() {
+		Some(reset) => reset.get("member"),
+		None => {
+			drop(transaction);
+			return Ok(Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.header("content-security-policy", "script-src 'none'")
+				.body(make_body("Reset Password", make_reset(false, None), client, session)?));
+		},
+	};
+	result!(transaction.execute("UPDATE scrap.member
+		SET hash=crypt($2, gen_salt('bf'))
+		WHERE id=$1",
+		&[&member, &password]));
+	result!(transaction.execute("DELETE FROM scrap.session
+		WHERE member=$1",
+		&[&member]));
+	result!(transaction.execute("DELETE FROM scrap.token
+		WHERE member=$1",
+		&[&member]));
+	result!(transaction.commit());
+	Ok(Response::builder()
+		.header("location", "/login")
+		.status(StatusCode::SEE_OTHER)
+		.body("".to_string()))
 }
 
-pub fn run(port: u16, pool: ClientPool) {
+pub fn run(port: u16, pool: ClientPool, static_path: PathBuf, base_url: String, notifier: Option<Arc<dyn Notifier>>, webhooks: Arc<Webhooks>) {
+	let assets = warp::get2().and(path("static")).and(peek())
+		.and(warp::header::optional::<String>("if-none-match"))
+		.and(warp::fs::dir(static_path.clone()))
+		.map(move |tail, if_none_match, file| get_static(&static_path, tail, if_none_match, file));
 	let client = any().map(move || pool.get().unwrap());
 	let session = warp::cookie::optional("session2")
+		.map(|cookie: Option<String>| cookie.filter(|cookie| !cookie.starts_with(TOKEN_PREFIX)).unwrap_or(String::new()));
+	// Read-only pages and flag submission also accept an API token instead of the session cookie.
+	let reader = warp::header::optional::<String>("authorization").and(session)
+		.map(|authorization: Option<String>, session: String| {
+			match authorization.as_ref().and_then(|authorization| authorization.strip_prefix("Bearer ")) {
+				Some(token) if token.starts_with(TOKEN_PREFIX) => token.to_string(),
+				_ => session,
+			}
+		});
+	let submission = warp::cookie::optional("submission")
 		.map(|cookie: Option<String>| cookie.unwrap_or(String::new()));
-	let invalid = warp::cookie::optional("invalid")
-		.map(|cookie: Option<String>| cookie.unwrap_or(String::new()));
+	let base_url = any().map(move || base_url.clone());
+	let notifier = any().map(move || notifier.clone());
+	let hub = Arc::new(Hub::default());
+	let subscriber = hub.clone();
+	let live = warp::get2().and(path("live")).and(end()).and(warp::sse())
+		.map(move |sse: warp::sse::Sse| sse.reply(warp::sse::keep_alive().stream(subscriber.subscribe())));
+	let hub = any().map(move || hub.clone());
+	let webhooks = any().map(move || webhooks.clone());
 	let get = warp::get2().and(client.clone()).and(session.clone());
 	let post = warp::post2().and(client.clone()).and(session.clone());
-	let routes = get.clone().and(end()).and_then(get_home)
-		.or(get.clone().and(path("challenges")).and(end()).and(invalid.clone()).and_then(get_challenges))
-		.or(get.clone().and(path("scoreboard")).and(end()).and_then(get_scoreboard))
-		.or(get.clone().and(path("pbr")).and(end()).and_then(get_pbr))
+	let read = warp::get2().and(client.clone()).and(reader);
+	let routes = assets
+		.or(live)
+		.or(read.clone().and(path("announcements")).and(end()).and_then(get_announcements))
+		.or(read.clone().and(end()).and_then(get_home))
+		.or(read.clone().and(path("challenges")).and(end()).and(submission)
+			.and(warp::query::<HashMap<String, String>>()).and_then(get_challenges))
+		.or(read.clone().and(path("challenges")).and(param::<String>()).and(end()).and_then(get_challenge))
+		.or(read.clone().and(path("scoreboard")).and(end())
+			.and(warp::query::<HashMap<String, String>>()).and_then(get_scoreboard))
+		.or(warp::get2().and(client.clone()).and(path("scoreboard")).and(path("live")).and(end()).and_then(get_scoreboard_live))
+		.or(warp::get2().and(client.clone()).and(path("scoreboard.json")).and(end())
+			.and(warp::query::<HashMap<String, String>>()).and_then(get_scoreboard_json))
+		.or(get.clone().and(path("teams")).and(param::<i32>()).and(end()).and_then(get_team))
+		.or(read.clone().and(path("pbr")).and(end()).and_then(get_pbr))
+		.or(read.clone().and(path("archive")).and(end()).and_then(get_archive))
+		.or(read.clone().and(path("archive")).and(param::<String>()).and(end()).and_then(get_season))
+		.or(get.clone().and(path("rewards")).and(end()).and_then(get_rewards))
 		.or(get.clone().and(path("profile")).and(end()).and_then(get_profile))
 		.or(get.clone().and(path("register")).and(end()).and_then(get_register))
 		.or(get.clone().and(path("login")).and(end()).and_then(get_login))
-		.or(get.clone().and(path("events")).and(end()).and_then(get_almanac))
+		.or(get.clone().and(path("login")).and(path("totp")).and(end()).and_then(get_login_totp))
+		.or(warp::get2().and(client.clone()).and(path("login")).and(path("discord")).and(end()).and_then(login_discord))
+		.or(get.clone().and(path("login")).and(path("discord")).and(path("callback")).and(end())
+			.and(warp::header::optional::<String>("user-agent"))
+			.and(warp::cookie::optional("oauth_state"))
+			.and(webhooks.clone())
+			.and(warp::query::<HashMap<String, String>>())
+			.and_then(discord_callback))
+		.or(read.clone().and(path("events")).and(end()).and_then(get_almanac))
+		.or(read.clone().and(path("events")).and(param::<String>()).and(end()).and_then(get_event))
+		.or(warp::get2().and(client.clone()).and(path("events.ics")).and(end())
+			.and(base_url.clone())
+			.and_then(get_calendar))
 		.or(get.clone().and(path("admin")).and(end()).and_then(get_admin))
-		.or(post.clone().and(path("challenges")).and(end())
+		.or(get.clone().and(path("admin")).and(path("winners.csv")).and(end()).and_then(get_winners_csv))
+		.or(get.clone().and(path("reset")).and(param::<String>()).and(end()).and_then(get_reset))
+		.or(warp::post2().and(client.clone()).and(reader).and(path("challenges")).and(end())
+			.and(hub.clone())
+			.and(webhooks.clone())
 			.and(body::content_length_limit(4096))
 			.and(body::form()).and_then(submit))
+		.or(warp::post2().and(client.clone()).and(reader).and(path("challenges.json")).and(end())
+			.and(hub.clone())
+			.and(webhooks.clone())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(submit_json))
+		.or(warp::post2().and(client.clone()).and(reader).and(path("challenges")).and(param::<String>()).and(end())
+			.and(hub.clone())
+			.and(webhooks.clone())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(submit_challenge))
+		.or(post.clone().and(path("archive")).and(param::<String>()).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(check_archive))
 		.or(post.clone().and(path("profile")).and(end())
 			.and(body::content_length_limit(4096))
 			.and(body::form()).and_then(edit))
 		.or(post.clone().and(path("register")).and(end())
+			.and(webhooks.clone())
 			.and(body::content_length_limit(4096))
 			.and(body::form()).and_then(register))
+		.or(post.clone().and(path("profile")).and(path("sessions")).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(revoke))
+		.or(post.clone().and(path("profile")).and(path("division")).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(change_division))
+		.or(post.clone().and(path("profile")).and(path("tokens")).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(manage_tokens))
+		.or(post.clone().and(path("profile")).and(path("team")).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(manage_team))
+		.or(post.clone().and(path("profile")).and(path("totp")).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(manage_totp))
+		.or(post.clone().and(path("login")).and(path("totp")).and(end())
+			.and(warp::cookie::optional("pending"))
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(login_totp))
 		.or(post.clone().and(path("login")).and(end())
+			.and(warp::header::optional::<String>("user-agent"))
 			.and(body::content_length_limit(4096))
 			.and(body::form()).and_then(login))
+		.or(post.clone().and(path("rewards")).and(path("redeem")).and(end())
+			.and(webhooks.clone())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(redeem))
 		.or(post.clone().and(path("admin")).and(end())
 			.and(body::content_length_limit(4096))
 			.and(body::form()).and_then(gib_tickets))
+		.or(post.clone().and(path("admin")).and(path("division")).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(approve_division))
+		.or(post.clone().and(path("admin")).and(path("team")).and(end())
+			.and(hub.clone())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(set_team_state))
+		.or(post.clone().and(path("admin")).and(path("adjustments")).and(end())
+			.and(hub.clone())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(adjust_score))
+		.or(post.clone().and(path("admin")).and(path("announcements")).and(end())
+			.and(hub.clone())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(manage_announcements))
+		.or(post.clone().and(path("admin")).and(path("inventory")).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(set_inventory))
+		.or(post.clone().and(path("admin")).and(path("raffle")).and(end()).and_then(draw_raffle))
+		.or(post.clone().and(path("admin")).and(path("raffle")).and(path("verify")).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(verify_raffle))
+		.or(post.clone().and(path("admin")).and(path("prizes")).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(fulfill_prize))
+		.or(post.clone().and(path("admin")).and(path("reset")).and(end())
+			.and(base_url.clone())
+			.and(notifier)
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(create_reset))
+		.or(post.clone().and(path("reset")).and(param::<String>()).and(end())
+			.and(body::content_length_limit(4096))
+			.and(body::form()).and_then(reset))
 		.or(get.clone().and(path("logout")).and(end()).and_then(logout))
 		.recover(error);
 	warp::serve(routes).run(([127, 0, 0, 1], port));
 }
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+	use super::*;
+
+	fn prize(name: &str, weight: f64, one_time: bool) -> CatalogPrize {
+		CatalogPrize { name: name.to_string(), weight, one_time }
+	}
+
+	fn draw<'a>(catalog: &'a [CatalogPrize], owned: &[&str], roll: f64) -> &'a str {
+		let owned: Vec<String> = owned.iter().map(|name| name.to_string()).collect();
+		&draw_prize(catalog, &owned, roll).unwrap().name
+	}
+
+	#[test]
+	fn draw_prize_gives_one_time_prizes_their_share() {
+		let catalog = [prize("Background", 30.0, false), prize("Role", 60.0, true), prize("Picture", 10.0, false)];
+		assert_eq!(draw(&catalog, &[], 0.29), "Background");
+		assert_eq!(draw(&catalog, &[], 0.31), "Role");
+		assert_eq!(draw(&catalog, &[], 0.89), "Role");
+		assert_eq!(draw(&catalog, &[], 0.91), "Picture");
+	}
+
+	#[test]
+	fn draw_prize_splits_owned_one_time_odds_by_weight() {
+		let catalog = [prize("Background", 30.0, false), prize("Role", 60.0, true), prize("Picture", 10.0, false)];
+		assert_eq!(draw(&catalog, &["Role"], 0.74), "Background");
+		assert_eq!(draw(&catalog, &["Role"], 0.76), "Picture");
+		assert_eq!(draw(&catalog, &["Role"], 0.999), "Picture");
+	}
+
+	#[test]
+	fn csv_field_quotes_only_when_needed() {
+		assert_eq!(csv_field("Steam Game"), "Steam Game");
+		assert_eq!(csv_field("a,b"), "\"a,b\"");
+		assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+		assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+		assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+	}
+
+	#[test]
+	fn ics_text_escapes_special_characters() {
+		assert_eq!(ics_text("a,b;c\\d"), "a\\,b\\;c\\\\d");
+		assert_eq!(ics_text("one\r\ntwo\nthree"), "one\\ntwo\\nthree");
+	}
+
+	#[test]
+	fn ics_line_folds_at_75_octets() {
+		let mut calendar = String::new();
+		ics_line(&mut calendar, &"x".repeat(75));
+		assert_eq!(calendar, format!("{}\r\n", "x".repeat(75)));
+
+		let mut calendar = String::new();
+		ics_line(&mut calendar, &"x".repeat(150));
+		assert_eq!(calendar, format!("{}\r\n {}\r\n {}\r\n", "x".repeat(75), "x".repeat(74), "x"));
+	}
+
+	#[test]
+	fn ics_line_does_not_split_characters() {
+		let mut calendar = String::new();
+		ics_line(&mut calendar, &format!("{}é", "x".repeat(74)));
+		assert_eq!(calendar, format!("{}\r\n é\r\n", "x".repeat(74)));
+		for line in calendar.split("\r\n") {
+			assert!(line.len() <= 75);
+		}
+	}
+
+	#[test]
+	fn outcome_codes_round_trip() {
+		let codes: HashSet<&str> = Outcome::ALL.iter().map(|outcome| outcome.code()).collect();
+		assert_eq!(codes.len(), Outcome::ALL.len());
+		for &outcome in &Outcome::ALL {
+			assert!(Outcome::parse(outcome.code()) == Some(outcome));
+		}
+		assert!(Outcome::parse("rate_limited") == Some(Outcome::RateLimited));
+		assert!(Outcome::parse("unknown").is_none());
+		assert!(Outcome::parse("").is_none());
+	}
+}
@@ -0,0 +1,70 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use totp_rs::{Algorithm, TOTP};
+
+const STEP: u64 = 30;
+
+fn totp(secret: &[u8]) -> TOTP {
+	TOTP::new_unchecked(Algorithm::SHA1, 6, 0, STEP, secret.to_vec())
+}
+
+/// Secret in the base32 form authenticator apps accept when typed in by hand.
+pub fn encode(secret: &[u8]) -> String {
+	totp(secret).get_secret_base32()
+}
+
+/// `otpauth://` URI that enrolls the secret in an authenticator app.
+pub fn url(issuer: &str, account: &str, secret: &[u8]) -> String {
+	format!("otpauth://totp/{}:{}?secret={}&issuer={}",
+		utf8_percent_encode(issuer, NON_ALPHANUMERIC),
+		utf8_percent_encode(account, NON_ALPHANUMERIC),
+		encode(secret),
+		utf8_percent_encode(issuer, NON_ALPHANUMERIC))
+}
+
+/// Returns the time step the code belongs to, allowing a step of clock drift either way.
+/// Steps up to `last_step` were already used, so callers store the step to stop codes being replayed.
+pub fn verify(secret: &[u8], code: &str, last_step: Option<i64>) -> Option<i64> {
+	verify_at(secret, code, last_step, SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+fn verify_at(secret: &[u8], code: &str, last_step: Option<i64>, time: u64) -> Option<i64> {
+	let now = time / STEP;
+	let totp = totp(secret);
+	(now - 1..=now + 1)
+		.filter(|&step| last_step.is_none_or(|last_step| step as i64 > last_step))
+		.find(|step| totp.check(code, step * STEP))
+		.map(|step| step as i64)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SECRET: &[u8] = b"12345678901234567890";
+	const TIME: u64 = 1_700_000_000;
+
+	fn code(time: u64) -> String {
+		totp(SECRET).generate(time)
+	}
+
+	#[test]
+	fn accepts_one_step_of_drift() {
+		let now = (TIME / STEP) as i64;
+		assert_eq!(verify_at(SECRET, &code(TIME), None, TIME), Some(now));
+		assert_eq!(verify_at(SECRET, &code(TIME - STEP), None, TIME), Some(now - 1));
+		assert_eq!(verify_at(SECRET, &code(TIME + STEP), None, TIME), Some(now + 1));
+		assert_eq!(verify_at(SECRET, &code(TIME - 2 * STEP), None, TIME), None);
+		assert_eq!(verify_at(SECRET, &code(TIME + 2 * STEP), None, TIME), None);
+	}
+
+	#[test]
+	fn rejects_used_steps() {
+		let now = (TIME / STEP) as i64;
+		let step = verify_at(SECRET, &code(TIME), None, TIME);
+		assert_eq!(verify_at(SECRET, &code(TIME), step, TIME), None);
+		assert_eq!(verify_at(SECRET, &code(TIME), step, TIME + STEP), None);
+		assert_eq!(verify_at(SECRET, &code(TIME - STEP), Some(now), TIME), None);
+		assert_eq!(verify_at(SECRET, &code(TIME + STEP), Some(now), TIME), Some(now + 1));
+	}
+}
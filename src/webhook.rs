@@ -0,0 +1,115 @@
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::Duration;
+use serde::Serialize;
+
+use crate::database::ClientPool;
+
+/// Attempts per delivery before an event is dropped, backing off exponentially from a second.
+const ATTEMPTS: u32 = 4;
+
+pub enum Format {
+	/// The event itself, as JSON.
+	Json,
+	/// A Discord webhook message describing the event.
+	Discord,
+}
+
+pub struct Target {
+	pub url: String,
+	pub format: Format,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+	Solve { team: String, member: Option<String>, challenge: String, value: i32 },
+	FirstBlood { team: String, member: Option<String>, challenge: String, value: i32 },
+	Registration { name: String },
+	Prize { team: String, member: String, discord: String, prize: String },
+	Start { title: String },
+	Stop { title: String },
+}
+
+impl Event {
+	fn message(&self) -> String {
+		match self {
+			Event::Solve { team, challenge, value, .. } => format!("{} solved {} ({} points)", team, challenge, value),
+			Event::FirstBlood { team, challenge, value, .. } => format!("First blood! {} solved {} ({} points)", team, challenge, value),
+			Event::Registration { name } => format!("{} registered", name),
+			// The Cyber Discord bot hands out prizes from messages in this form.
+			Event::Prize { team, discord, prize, .. } => format!("##prize \"{}\" \"{}\" \"{}\"", team, discord, prize),
+			Event::Start { title } => format!("{} has started!", title),
+			Event::Stop { title } => format!("{} is over!", title),
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct DiscordMessage {
+	content: String,
+	allowed_mentions: AllowedMentions,
+}
+
+/// Keeps team names like `@everyone` from pinging anyone.
+#[derive(Serialize)]
+struct AllowedMentions {
+	parse: [&'static str; 0],
+}
+
+/// Delivers events to every target from a background thread so requests never wait on them.
+pub struct Webhooks {
+	sender: Sender<Event>,
+}
+
+impl Webhooks {
+	pub fn new(targets: Vec<Target>) -> Webhooks {
+		let (sender, receiver) = channel::<Event>();
+		thread::spawn(move || for event in receiver {
+			for target in &targets {
+				for attempt in 0..ATTEMPTS {
+					let request = ureq::post(&target.url).timeout(Duration::from_secs(10));
+					let response = match target.format {
+						Format::Json => request.send_json(&event),
+						Format::Discord => request.send_json(DiscordMessage {
+							content: event.message(),
+							allowed_mentions: AllowedMentions { parse: [] },
+						}),
+					};
+					match response {
+						Ok(_) => break,
+						// Other client errors will not succeed on a retry.
+						Err(ureq::Error::Status(status, _)) if status < 500 && status != 429 => {
+							eprintln!("Webhook {} rejected event with {}", target.url, status);
+							break;
+						},
+						Err(e) if attempt + 1 == ATTEMPTS => eprintln!("Failed to deliver webhook to {}: {}", target.url, e),
+						Err(_) => thread::sleep(Duration::from_secs(1 << attempt)),
+					}
+				}
+			}
+		});
+		Webhooks { sender }
+	}
+
+	pub fn send(&self, event: Event) {
+		let _ = self.sender.send(event);
+	}
+}
+
+/// Sends start and stop events once the CTF crosses its configured times, at most an hour late.
+pub fn check_schedule(pool: &ClientPool, webhooks: &Webhooks) -> Result<(), Box<dyn Error>> {
+	let mut client = pool.get()?;
+	for row in client.query("UPDATE scrap.ctf SET notified_start=start
+		WHERE current AND start <= NOW() AND start > NOW() - INTERVAL '1 hour' AND notified_start IS DISTINCT FROM start
+		RETURNING title", &[])? {
+		webhooks.send(Event::Start { title: row.get("title") });
+	}
+	for row in client.query("UPDATE scrap.ctf SET notified_stop=stop
+		WHERE current AND stop <= NOW() AND stop > NOW() - INTERVAL '1 hour' AND notified_stop IS DISTINCT FROM stop
+		RETURNING title", &[])? {
+		webhooks.send(Event::Stop { title: row.get("title") });
+	}
+	Ok(())
+}
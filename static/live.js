@@ -0,0 +1,24 @@
+const live = new EventSource('/live');
+
+live.addEventListener('announcement', (event) => {
+    const announcement = JSON.parse(event.data);
+    const container = announcement.challenge === null
+        ? document.getElementById('announcements')
+        : document.querySelector(`.announcements[data-challenge="${CSS.escape(announcement.challenge)}"]`);
+    if (container === null) {
+        return;
+    }
+    const p = document.createElement('p');
+    const time = document.createElement('strong');
+    time.textContent = announcement.time;
+    p.append(time, ' ', announcement.message);
+    container.prepend(p);
+});
+
+// Every open scoreboard gets the event at once, so reloads are spread out and a burst of solves only reloads once.
+let reload = null;
+live.addEventListener('scoreboard', () => {
+    if (document.location.pathname === '/scoreboard' && reload === null) {
+        reload = setTimeout(() => document.location.reload(), 5000 + Math.random() * 25000);
+    }
+});
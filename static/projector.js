@@ -0,0 +1,45 @@
+const projector = new EventSource('/live');
+const teams = 20;
+const solves = 10;
+
+function cell(row, value) {
+    const td = document.createElement('td');
+    td.textContent = value;
+    row.append(td);
+}
+
+projector.addEventListener('scoreboard', (event) => {
+    const rows = JSON.parse(event.data).standings.slice(0, teams).map((standing) => {
+        const row = document.createElement('tr');
+        cell(row, standing.pos);
+        cell(row, standing.team);
+        cell(row, standing.score);
+        return row;
+    });
+    document.querySelector('.standings tbody').replaceChildren(...rows);
+});
+
+projector.addEventListener('solve', (event) => {
+    const solve = JSON.parse(event.data);
+    const list = document.querySelector('.solves ul');
+    const item = document.createElement('li');
+    const time = document.createElement('strong');
+    time.textContent = solve.time;
+    item.append(time, ` ${solve.team} solved ${solve.challenge}`);
+    list.prepend(item);
+    while (list.children.length > solves) {
+        list.lastElementChild.remove();
+    }
+});
+
+projector.addEventListener('announcement', (event) => {
+    const announcement = JSON.parse(event.data);
+    if (announcement.challenge !== null) {
+        return;
+    }
+    const p = document.createElement('p');
+    const time = document.createElement('strong');
+    time.textContent = announcement.time;
+    p.append(time, ' ', announcement.message);
+    document.getElementById('announcements').prepend(p);
+});